@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE login_failures(
+    scope TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    failed_attempts INTEGER NOT NULL,
+    last_failed_at timestamptz NOT NULL,
+    locked_until timestamptz NULL,
+    PRIMARY KEY (scope, subject)
+);
+
+CREATE TABLE login_audit_log(
+    id uuid PRIMARY KEY,
+    occurred_at timestamptz NOT NULL,
+    username TEXT NOT NULL,
+    ip_address TEXT NOT NULL,
+    event TEXT NOT NULL
+);
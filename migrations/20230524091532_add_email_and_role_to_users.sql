@@ -0,0 +1,7 @@
+-- Add migration script here
+-- Users created by hand before invitations existed are the instance owners
+BEGIN;
+    ALTER TABLE users ADD COLUMN email TEXT NULL UNIQUE;
+    ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'owner';
+    ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+COMMIT;
@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE user_tokens(
+    token_hash TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    email TEXT NOT NULL,
+    user_id uuid NULL
+        REFERENCES users (user_id),
+    created_by uuid NULL
+        REFERENCES users (user_id),
+    requested_from TEXT NULL,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz NULL,
+    PRIMARY KEY (token_hash)
+);
@@ -0,0 +1,27 @@
+CREATE TABLE email_deliveries(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    recipient TEXT NOT NULL,
+    message_id TEXT NULL,
+    transaction_id TEXT NULL,
+    sent_at timestamptz NOT NULL
+);
+CREATE INDEX email_deliveries_message_id_idx ON email_deliveries (message_id);
+CREATE INDEX email_deliveries_transaction_id_idx ON email_deliveries (transaction_id);
+
+CREATE TABLE email_events(
+    id uuid NOT NULL,
+    PRIMARY KEY (id),
+    delivery_id uuid NULL
+        REFERENCES email_deliveries (id),
+    provider TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    recipient TEXT NOT NULL,
+    detail TEXT NULL,
+    -- Providers retry webhooks, identical payloads are only recorded once
+    payload_hash TEXT NOT NULL,
+    received_at timestamptz NOT NULL,
+    UNIQUE (provider, payload_hash)
+);
+
+ALTER TABLE subscriptions ADD COLUMN soft_bounces INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+CREATE TABLE suppressions(
+    -- Lowercased, so that differently cased spellings of an address match
+    email TEXT NOT NULL,
+    PRIMARY KEY (email),
+    reason TEXT NOT NULL,
+    source TEXT NOT NULL,
+    note TEXT NULL,
+    created_at timestamptz NOT NULL
+);
@@ -0,0 +1,2 @@
+-- Provider notifications and suppressions may spell an address with different case
+CREATE INDEX subscriptions_lower_email_idx ON subscriptions (lower(email));
@@ -0,0 +1,2 @@
+-- Addresses typed into the password reset and invitation forms may differ in case
+CREATE INDEX users_lower_email_idx ON users (lower(email));
@@ -0,0 +1,544 @@
+use std::{net::IpAddr, sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
+    PasswordVerifier, Version,
+};
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{
+        header::{AUTHORIZATION, RETRY_AFTER, WWW_AUTHENTICATE},
+        request::Parts,
+        HeaderMap, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use tracing::{error, instrument, warn};
+use uuid::Uuid;
+
+use crate::{
+    client_ip::ClientIp,
+    configuration::LoginSettings,
+    domain::{NewPassword, SubscriberEmail, Username},
+    startup::AppState,
+    telemetry::spawn_blocking_with_tracing,
+};
+
+// Verified against when the username doesn't exist, so that unknown usernames
+// go through the same argon2 verification as known ones and take as long.
+const FALLBACK_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=15000,t=2,p=1$XwQjajNsM4LK7zyoN7S8uw$IMrqr1jL2Lx42+la8UBZQ8AzOH748/xwOWfUtZBMZC8";
+
+pub struct Credentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum AuthError {
+    #[error("Invalid credentials")]
+    InvalidCredentials,
+    #[error("Too many failed login attempts, retry in {} seconds", .retry_after.as_secs())]
+    LockedOut { retry_after: Duration },
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserRole {
+    Owner,
+    Admin,
+}
+
+impl UserRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UserRole::Owner => "owner",
+            UserRole::Admin => "admin",
+        }
+    }
+}
+
+impl TryFrom<String> for UserRole {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> std::result::Result<Self, Self::Error> {
+        match value.as_str() {
+            "owner" => Ok(UserRole::Owner),
+            "admin" => Ok(UserRole::Admin),
+            other => anyhow::bail!("Unknown user role {other}"),
+        }
+    }
+}
+
+/// A user authenticated through HTTP Basic authentication, subject to the same
+/// lockout rules as `POST /login`.
+#[derive(Debug)]
+pub struct AuthenticatedUser {
+    pub user_id: Uuid,
+    pub role: UserRole,
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for AuthenticatedUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let ClientIp(ip) = ClientIp::from_request_parts(parts, state).await?;
+        let credentials = basic_authentication(&parts.headers).map_err(|_| unauthorized())?;
+        let user_id = validate_credentials(
+            &state.connection,
+            &state.settings.load().login,
+            credentials,
+            ip,
+        )
+        .await
+        .map_err(|e| match e {
+            AuthError::InvalidCredentials => unauthorized(),
+            AuthError::LockedOut { retry_after } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(RETRY_AFTER, retry_after.as_secs().max(1).to_string())],
+            )
+                .into_response(),
+            AuthError::Unexpected(e) => {
+                error!("Failed to validate credentials: {e:?}");
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        })?;
+        let role = get_role(&state.connection, user_id).await.map_err(|e| {
+            error!("Failed to retrieve user role: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?;
+        Ok(Self { user_id, role })
+    }
+}
+
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(WWW_AUTHENTICATE, r#"Basic realm="admin""#)],
+    )
+        .into_response()
+}
+
+fn basic_authentication(headers: &HeaderMap) -> Result<Credentials> {
+    let header_value = headers
+        .get(AUTHORIZATION)
+        .context("The 'Authorization' header is missing")?
+        .to_str()
+        .context("The 'Authorization' header is not valid UTF-8")?;
+    let encoded = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme is not 'Basic'")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .context("Failed to base64-decode 'Basic' credentials")?;
+    let decoded = String::from_utf8(decoded).context("Credentials are not valid UTF-8")?;
+    let (username, password) = decoded
+        .split_once(':')
+        .context("'Basic' credentials must contain a ':'")?;
+    Ok(Credentials {
+        username: username.to_string(),
+        password: Secret::new(password.to_string()),
+    })
+}
+
+#[instrument(name = "Get user role", skip(connection))]
+async fn get_role(connection: &PgPool, user_id: Uuid) -> Result<UserRole> {
+    let role = sqlx::query!(
+        r#"
+        SELECT role
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(connection)
+    .await
+    .context("Failed to retrieve user role")?
+    .role;
+    UserRole::try_from(role)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum FailureScope {
+    Account,
+    Ip,
+}
+
+impl FailureScope {
+    fn as_str(&self) -> &'static str {
+        match self {
+            FailureScope::Account => "account",
+            FailureScope::Ip => "ip",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum AuditEvent {
+    LoginSucceeded,
+    LoginFailed,
+    LoginRejectedLockedOut,
+    AccountLocked,
+    IpLocked,
+}
+
+impl AuditEvent {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AuditEvent::LoginSucceeded => "login_succeeded",
+            AuditEvent::LoginFailed => "login_failed",
+            AuditEvent::LoginRejectedLockedOut => "login_rejected_locked_out",
+            AuditEvent::AccountLocked => "account_locked",
+            AuditEvent::IpLocked => "ip_locked",
+        }
+    }
+}
+
+#[instrument(
+    name = "Validate credentials",
+    skip(connection, settings, credentials),
+    fields(username = %credentials.username)
+)]
+pub async fn validate_credentials(
+    connection: &PgPool,
+    settings: &LoginSettings,
+    credentials: Credentials,
+    ip: IpAddr,
+) -> Result<Uuid, AuthError> {
+    let username = credentials.username;
+    let ip = ip.to_string();
+
+    if let Some(retry_after) = lockout_remaining(connection, &username, &ip).await? {
+        record_audit_event(
+            connection,
+            &username,
+            &ip,
+            AuditEvent::LoginRejectedLockedOut,
+        )
+        .await?;
+        return Err(AuthError::LockedOut { retry_after });
+    }
+
+    let (user_id, expected_password_hash) =
+        match get_stored_credentials(connection, &username).await? {
+            Some((user_id, password_hash)) => (Some(user_id), password_hash),
+            None => (None, Secret::new(FALLBACK_PASSWORD_HASH.to_string())),
+        };
+    let password = credentials.password;
+    let password_matches =
+        spawn_blocking_with_tracing(move || verify_password_hash(expected_password_hash, password))
+            .await
+            .context("Failed to spawn blocking task")??;
+
+    match user_id {
+        Some(user_id) if password_matches => {
+            clear_failures(connection, FailureScope::Account, &username).await?;
+            record_audit_event(connection, &username, &ip, AuditEvent::LoginSucceeded).await?;
+            Ok(user_id)
+        }
+        _ => {
+            record_audit_event(connection, &username, &ip, AuditEvent::LoginFailed).await?;
+            let account_threshold = settings.max_failed_attempts_per_account;
+            if record_failure(
+                connection,
+                settings,
+                FailureScope::Account,
+                &username,
+                account_threshold,
+            )
+            .await?
+            {
+                record_audit_event(connection, &username, &ip, AuditEvent::AccountLocked).await?;
+            }
+            let ip_threshold = settings.max_failed_attempts_per_ip;
+            if record_failure(connection, settings, FailureScope::Ip, &ip, ip_threshold).await? {
+                record_audit_event(connection, &username, &ip, AuditEvent::IpLocked).await?;
+            }
+            Err(AuthError::InvalidCredentials)
+        }
+    }
+}
+
+/// Stores a user who can log in right away, e.g. the first owner of an instance.
+#[instrument(name = "Creating a user", skip(connection, password))]
+pub async fn create_user(
+    connection: &PgPool,
+    username: &Username,
+    email: &SubscriberEmail,
+    password: NewPassword,
+    role: UserRole,
+) -> Result<Uuid> {
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password.into_secret()))
+            .await
+            .context("Failed to spawn blocking task")??;
+    let user_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, email, role)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        username.as_ref(),
+        password_hash.expose_secret(),
+        email.as_ref(),
+        role.as_str(),
+    )
+    .execute(connection)
+    .await
+    .context("Failed to insert user")?;
+    Ok(user_id)
+}
+
+pub fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = argon2_hasher()?
+        .hash_password(password.expose_secret().as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {e}"))?
+        .to_string();
+    Ok(Secret::new(password_hash))
+}
+
+fn argon2_hasher() -> Result<Argon2<'static>> {
+    let params = Params::new(15000, 2, 1, None)
+        .map_err(|e| anyhow::anyhow!("Invalid argon2 parameters: {e}"))?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+#[instrument(name = "Verify password hash", skip(expected_password_hash, password))]
+fn verify_password_hash(
+    expected_password_hash: Secret<String>,
+    password: Secret<String>,
+) -> Result<bool> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
+        .map_err(|e| anyhow::anyhow!("Failed to parse stored password hash: {e}"))?;
+    match Argon2::default()
+        .verify_password(password.expose_secret().as_bytes(), &expected_password_hash)
+    {
+        Ok(()) => Ok(true),
+        Err(argon2::password_hash::Error::Password) => Ok(false),
+        Err(e) => Err(anyhow::anyhow!("Failed to verify password hash: {e}")),
+    }
+}
+
+#[instrument(name = "Get stored credentials", skip(connection))]
+async fn get_stored_credentials(
+    connection: &PgPool,
+    username: &str,
+) -> Result<Option<(Uuid, Secret<String>)>> {
+    let row = sqlx::query!(
+        r#"
+        SELECT user_id, password_hash
+        FROM users
+        WHERE username = $1
+        "#,
+        username,
+    )
+    .fetch_optional(connection)
+    .await
+    .context("Failed to retrieve stored credentials")?
+    .map(|row| (row.user_id, Secret::new(row.password_hash)));
+    Ok(row)
+}
+
+/// Returns how long the account or the ip address is still locked out for, if at all.
+#[instrument(name = "Check for lockout", skip(connection))]
+async fn lockout_remaining(
+    connection: &PgPool,
+    username: &str,
+    ip: &str,
+) -> Result<Option<Duration>> {
+    let locked_until = sqlx::query!(
+        r#"
+        SELECT MAX(locked_until) AS locked_until
+        FROM login_failures
+        WHERE ((scope = 'account' AND subject = $1) OR (scope = 'ip' AND subject = $2))
+            AND locked_until > $3
+        "#,
+        username,
+        ip,
+        Utc::now(),
+    )
+    .fetch_one(connection)
+    .await
+    .context("Failed to check for lockouts")?
+    .locked_until;
+    Ok(locked_until.and_then(|locked_until| (locked_until - Utc::now()).to_std().ok()))
+}
+
+/// Counts a failed attempt against the subject, locking it out once the threshold is reached.
+/// Returns whether the subject is now locked out.
+#[instrument(name = "Record failed login attempt", skip(connection, settings))]
+async fn record_failure(
+    connection: &PgPool,
+    settings: &LoginSettings,
+    scope: FailureScope,
+    subject: &str,
+    threshold: i32,
+) -> Result<bool> {
+    let now = Utc::now();
+    let window_start = now - chrono::Duration::from_std(settings.failure_window())?;
+    // The window is measured from the end of the last lockout as well, so that the
+    // backoff keeps growing for subjects that resume failing right after a lockout.
+    let failed_attempts = sqlx::query!(
+        r#"
+        INSERT INTO login_failures (scope, subject, failed_attempts, last_failed_at)
+        VALUES ($1, $2, 1, $3)
+        ON CONFLICT (scope, subject) DO UPDATE SET
+            failed_attempts = CASE
+                WHEN GREATEST(login_failures.last_failed_at, login_failures.locked_until) < $4 THEN 1
+                ELSE login_failures.failed_attempts + 1
+            END,
+            last_failed_at = EXCLUDED.last_failed_at
+        RETURNING failed_attempts
+        "#,
+        scope.as_str(),
+        subject,
+        now,
+        window_start,
+    )
+    .fetch_one(connection)
+    .await
+    .context("Failed to record failed login attempt")?
+    .failed_attempts;
+
+    let lockout = match settings.lockout_duration(failed_attempts, threshold) {
+        Some(lockout) => lockout,
+        None => return Ok(false),
+    };
+    warn!(
+        "Locking out {} {subject} for {} seconds after {failed_attempts} failed login attempts",
+        scope.as_str(),
+        lockout.as_secs()
+    );
+    sqlx::query!(
+        r#"
+        UPDATE login_failures
+        SET locked_until = $3
+        WHERE scope = $1 AND subject = $2
+        "#,
+        scope.as_str(),
+        subject,
+        now + chrono::Duration::from_std(lockout)?,
+    )
+    .execute(connection)
+    .await
+    .context("Failed to lock out")?;
+    Ok(true)
+}
+
+/// Lifts an account lockout, e.g. once its owner has proven control of the account's email address.
+pub async fn reset_account_lockout(connection: &PgPool, username: &str) -> Result<()> {
+    clear_failures(connection, FailureScope::Account, username).await
+}
+
+#[instrument(name = "Clear failed login attempts", skip(connection))]
+async fn clear_failures(connection: &PgPool, scope: FailureScope, subject: &str) -> Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM login_failures
+        WHERE scope = $1 AND subject = $2
+        "#,
+        scope.as_str(),
+        subject,
+    )
+    .execute(connection)
+    .await
+    .context("Failed to clear failed login attempts")?;
+    Ok(())
+}
+
+#[instrument(name = "Record login audit event", skip(connection))]
+async fn record_audit_event(
+    connection: &PgPool,
+    username: &str,
+    ip: &str,
+    event: AuditEvent,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO login_audit_log (id, occurred_at, username, ip_address, event)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        username,
+        ip,
+        event.as_str(),
+    )
+    .execute(connection)
+    .await
+    .context("Failed to record login audit event")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_none, assert_ok_eq, assert_some_eq};
+    use secrecy::{ExposeSecret, Secret};
+
+    use crate::{
+        authentication::{compute_password_hash, verify_password_hash, FALLBACK_PASSWORD_HASH},
+        configuration::LoginSettings,
+    };
+
+    fn settings() -> LoginSettings {
+        LoginSettings {
+            max_failed_attempts_per_account: 5,
+            max_failed_attempts_per_ip: 20,
+            failure_window_seconds: 900,
+            lockout_base_seconds: 30,
+            lockout_max_seconds: 3600,
+        }
+    }
+
+    #[test]
+    fn no_lockout_below_the_threshold() {
+        assert_none!(settings().lockout_duration(4, 5));
+    }
+
+    #[test]
+    fn lockout_doubles_with_every_failure_past_the_threshold() {
+        let settings = settings();
+        assert_some_eq!(settings.lockout_duration(5, 5).map(|d| d.as_secs()), 30);
+        assert_some_eq!(settings.lockout_duration(6, 5).map(|d| d.as_secs()), 60);
+        assert_some_eq!(settings.lockout_duration(8, 5).map(|d| d.as_secs()), 240);
+    }
+
+    #[test]
+    fn lockout_is_capped() {
+        assert_some_eq!(
+            settings().lockout_duration(1000, 5).map(|d| d.as_secs()),
+            3600
+        );
+    }
+
+    #[test]
+    fn password_hash_round_trips() {
+        let password = Secret::new("correct horse battery staple".to_string());
+        let hash = compute_password_hash(password.clone()).unwrap();
+        assert_ok_eq!(verify_password_hash(hash.clone(), password), true);
+        assert_ok_eq!(
+            verify_password_hash(hash, Secret::new("wrong".to_string())),
+            false
+        );
+    }
+
+    #[test]
+    fn fallback_hash_uses_the_same_parameters_as_real_hashes() {
+        let hash = compute_password_hash(Secret::new("password".to_string())).unwrap();
+        let params = |h: &str| h.split('$').take(4).collect::<Vec<_>>().join("$");
+        assert_eq!(params(FALLBACK_PASSWORD_HASH), params(hash.expose_secret()));
+    }
+}
@@ -0,0 +1,21 @@
+use std::net::TcpListener;
+
+use zero2prod::{
+    mailsink::{run, MailSink},
+    telemetry::{get_subscriber, init_subscriber},
+};
+
+/// Point `email_client.base_url` at the address it listens on, 127.0.0.1:8025
+/// unless set through MAILSINK_ADDRESS, and open it in a browser to see what was sent.
+#[tokio::main]
+async fn main() -> std::io::Result<()> {
+    let subscriber = get_subscriber("zero2prod-mailsink".into(), "info".into(), std::io::stdout);
+    init_subscriber(subscriber);
+
+    let address = std::env::var("MAILSINK_ADDRESS").unwrap_or_else(|_| "127.0.0.1:8025".into());
+    let listener = TcpListener::bind(address)?;
+    run(listener, MailSink::default())
+        .map_err(|e| std::io::Error::other(e.to_string()))?
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))
+}
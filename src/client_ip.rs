@@ -0,0 +1,128 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap},
+    response::{IntoResponse, Response},
+};
+
+use crate::{configuration::ClientIpSettings, startup::AppState};
+
+/// The address of the client, looking through trusted proxies.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl FromRequestParts<Arc<AppState>> for ClientIp {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let ConnectInfo(address) = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        Ok(Self(client_ip(
+            &state.client_ip,
+            address.ip(),
+            &parts.headers,
+        )))
+    }
+}
+
+/// Walks the forwarding header from the right, as every proxy appends the address it
+/// received the request from, and stops at the first hop that isn't a trusted proxy.
+/// Entries further left are whatever the client sent, so they are never believed.
+fn client_ip(settings: &ClientIpSettings, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+    let trusted = |ip: &IpAddr| settings.trusted_proxies.iter().any(|net| net.contains(ip));
+    if !trusted(&peer) {
+        return peer;
+    }
+    let mut client = peer;
+    let hops = headers
+        .get_all(settings.header.as_str())
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    for hop in hops.into_iter().rev() {
+        match hop.trim().parse::<IpAddr>() {
+            Ok(ip) => {
+                client = ip;
+                if !trusted(&ip) {
+                    break;
+                }
+            }
+            // Can't tell who sent it, the last hop we could tell is the best guess
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use axum::http::{HeaderMap, HeaderValue};
+
+    use super::client_ip;
+    use crate::configuration::ClientIpSettings;
+
+    fn settings() -> ClientIpSettings {
+        ClientIpSettings {
+            header: "X-Forwarded-For".into(),
+            trusted_proxies: vec!["10.0.0.0/8".parse().unwrap()],
+        }
+    }
+
+    fn forwarded_for(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forwarded-For", HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn ip(ip: &str) -> IpAddr {
+        ip.parse().unwrap()
+    }
+
+    #[test]
+    fn the_header_is_ignored_when_the_peer_is_not_a_trusted_proxy() {
+        let headers = forwarded_for("203.0.113.7");
+        assert_eq!(
+            client_ip(&settings(), ip("198.51.100.1"), &headers),
+            ip("198.51.100.1")
+        );
+    }
+
+    #[test]
+    fn the_right_most_untrusted_entry_is_the_client() {
+        let headers = forwarded_for("192.0.2.66, 203.0.113.7, 10.1.2.3");
+        assert_eq!(
+            client_ip(&settings(), ip("10.0.0.1"), &headers),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn a_trusted_proxy_without_the_header_is_the_client() {
+        assert_eq!(
+            client_ip(&settings(), ip("10.0.0.1"), &HeaderMap::new()),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn parsing_stops_at_a_garbled_entry() {
+        let headers = forwarded_for("192.0.2.66, unknown, 10.1.2.3");
+        assert_eq!(
+            client_ip(&settings(), ip("10.0.0.1"), &headers),
+            ip("10.1.2.3")
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use std::fmt::Write;
+
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+
+use super::EffectiveConfiguration;
+
+const REDACTED: &str = "[REDACTED]";
+
+fn join(table: &str, key: &str) -> String {
+    if table.is_empty() {
+        key.to_string()
+    } else {
+        format!("{table}.{key}")
+    }
+}
+
+fn is_table_array(value: &Value) -> bool {
+    matches!(value, Value::Array(items) if items.iter().any(Value::is_object))
+}
+
+impl EffectiveConfiguration {
+    fn display_value(&self, key: &str, value: &Value) -> Value {
+        if self.is_secret(key) && !value.is_null() {
+            REDACTED.into()
+        } else {
+            value.clone()
+        }
+    }
+
+    /// The settings as TOML, each value commented with where it came from.
+    pub fn to_toml(&self) -> Result<String> {
+        let mut toml = String::new();
+        match serde_json::to_value(&self.settings)? {
+            Value::Object(settings) => self.write_toml_table(&mut toml, "", &settings)?,
+            _ => unreachable!("Settings serialize to a table"),
+        }
+        Ok(toml)
+    }
+
+    fn write_toml_table(
+        &self,
+        toml: &mut String,
+        table: &str,
+        values: &Map<String, Value>,
+    ) -> Result<()> {
+        for (key, value) in values {
+            if value.is_object() || is_table_array(value) {
+                continue;
+            }
+            let path = join(table, key);
+            if value.is_null() {
+                writeln!(toml, "# {key} is not set")?;
+            } else {
+                let value = toml::Value::try_from(self.display_value(&path, value))?;
+                writeln!(toml, "{key} = {value}  # {}", self.source_of(&path))?;
+            }
+        }
+        for (key, value) in values {
+            let path = join(table, key);
+            match value {
+                Value::Object(values) => {
+                    writeln!(toml, "\n[{path}]")?;
+                    self.write_toml_table(toml, &path, values)?;
+                }
+                Value::Array(items) if is_table_array(value) => {
+                    for (i, item) in items.iter().enumerate() {
+                        writeln!(toml, "\n[[{path}]]")?;
+                        if let Value::Object(values) = item {
+                            self.write_toml_table(toml, &format!("{path}[{i}]"), values)?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// The settings as JSON, each value as `{"value": ..., "source": ...}`.
+    pub fn to_json(&self) -> Result<String> {
+        let settings = serde_json::to_value(&self.settings)?;
+        Ok(serde_json::to_string_pretty(
+            &self.annotate_json("", &settings),
+        )?)
+    }
+
+    fn annotate_json(&self, key: &str, value: &Value) -> Value {
+        match value {
+            Value::Object(values) => values
+                .iter()
+                .map(|(child, value)| (child.clone(), self.annotate_json(&join(key, child), value)))
+                .collect::<Map<_, _>>()
+                .into(),
+            Value::Array(items) if is_table_array(value) => items
+                .iter()
+                .enumerate()
+                .map(|(i, item)| self.annotate_json(&format!("{key}[{i}]"), item))
+                .collect(),
+            value => json!({
+                "value": self.display_value(key, value),
+                "source": self.source_of(key),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::configuration::{get_effective_configuration, ConfigArgs, EffectiveConfiguration};
+
+    fn with_extra_file(toml: &str) -> (EffectiveConfiguration, PathBuf) {
+        let extra = std::env::temp_dir().join(format!("{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&extra, toml).unwrap();
+        let config = get_effective_configuration(&ConfigArgs {
+            config_dir: Some(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("configuration")),
+            extra_files: vec![extra.clone()],
+        });
+        std::fs::remove_file(&extra).unwrap();
+        (config.unwrap(), extra)
+    }
+
+    #[test]
+    fn values_are_annotated_with_the_layer_that_set_them() {
+        let (config, extra) = with_extra_file("[application]\nport = 9123\n");
+
+        let toml = config.to_toml().unwrap();
+
+        assert!(toml.contains(&format!("port = 9123  # {}", extra.display())));
+        assert!(toml.contains("local.toml"));
+        assert!(toml.contains("log_level = \"info\"  # "));
+        assert!(toml.contains("fallbacks = []  # default"));
+        assert_eq!(config.source_of("environment"), "ZERO2PROD_ENV");
+    }
+
+    #[test]
+    fn secrets_are_redacted() {
+        let (config, _) = with_extra_file(
+            "[[email_client.fallbacks]]\nprovider = \"elastic_email\"\napi_key = \"fallback-key\"\n",
+        );
+
+        let toml = config.to_toml().unwrap();
+        let json = config.to_json().unwrap();
+
+        for shown in [&toml, &json] {
+            assert!(!shown.contains("long-and-very-secret-random-key"));
+            assert!(!shown.contains("fallback-key"));
+            assert!(shown.contains("[REDACTED]"));
+        }
+        assert!(toml.contains("[[email_client.fallbacks]]"));
+        let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            json["email_client"]["fallbacks"][0]["api_key"]["value"],
+            "[REDACTED]"
+        );
+        assert_eq!(json["application"]["hmac_secret"]["value"], "[REDACTED]");
+    }
+}
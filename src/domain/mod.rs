@@ -1,7 +1,11 @@
+mod new_password;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
+mod username;
 
+pub use new_password::NewPassword;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use username::Username;
@@ -0,0 +1,46 @@
+use anyhow::Result;
+use secrecy::{ExposeSecret, Secret};
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug)]
+pub struct NewPassword(Secret<String>);
+
+impl NewPassword {
+    pub fn parse(s: Secret<String>) -> Result<NewPassword> {
+        let length = s.expose_secret().graphemes(true).count();
+        if !(12..=128).contains(&length) {
+            anyhow::bail!("Passwords must be between 12 and 128 characters long");
+        } else {
+            Ok(Self(s))
+        }
+    }
+
+    pub fn into_secret(self) -> Secret<String> {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::*;
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    #[test]
+    fn a_password_shorter_than_12_graphemes_is_rejected() {
+        let password = Secret::new("a".repeat(11));
+        assert_err!(NewPassword::parse(password));
+    }
+
+    #[test]
+    fn a_password_longer_than_128_graphemes_is_rejected() {
+        let password = Secret::new("a".repeat(129));
+        assert_err!(NewPassword::parse(password));
+    }
+
+    #[test]
+    fn a_valid_password_is_parsed_successfully() {
+        let password = Secret::new("correct horse battery staple".to_string());
+        assert_ok!(NewPassword::parse(password));
+    }
+}
@@ -0,0 +1,59 @@
+use anyhow::Result;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Debug)]
+pub struct Username(String);
+
+impl Username {
+    pub fn parse(s: String) -> Result<Username> {
+        let is_empty = s.is_empty();
+        let is_too_long = s.graphemes(true).count() > 64;
+        let contains_forbidden_characters = s.chars().any(|c| c.is_whitespace() || c.is_control());
+        if is_empty || is_too_long || contains_forbidden_characters {
+            anyhow::bail!("Invalid username");
+        } else {
+            Ok(Self(s))
+        }
+    }
+}
+impl AsRef<str> for Username {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::*;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn a_64_grapheme_long_username_is_valid() {
+        let username = "a".repeat(64);
+        assert_ok!(Username::parse(username));
+    }
+
+    #[test]
+    fn a_username_longer_than_64_graphemes_is_rejected() {
+        let username = "a".repeat(65);
+        assert_err!(Username::parse(username));
+    }
+
+    #[test]
+    fn empty_string_is_rejected() {
+        let username = "".to_string();
+        assert_err!(Username::parse(username));
+    }
+
+    #[test]
+    fn usernames_containing_whitespace_are_rejected() {
+        let username = "ursula le guin".to_string();
+        assert_err!(Username::parse(username));
+    }
+
+    #[test]
+    fn a_valid_username_is_parsed_successfully() {
+        let username = "ursula".to_string();
+        assert_ok!(Username::parse(username));
+    }
+}
@@ -1,186 +0,0 @@
-use anyhow::Result;
-use reqwest::Client;
-use secrecy::{ExposeSecret, Secret};
-use serde::Serialize;
-
-use crate::domain::SubscriberEmail;
-
-pub struct EmailClient {
-    http_client: Client,
-    base_url: String,
-    sender: SubscriberEmail,
-    api_key: Secret<String>,
-}
-
-impl EmailClient {
-    pub fn new(
-        base_url: String,
-        sender: SubscriberEmail,
-        api_key: Secret<String>,
-        timeout: std::time::Duration,
-    ) -> Self {
-        Self {
-            http_client: Client::builder()
-                .timeout(timeout)
-                .build()
-                .expect("Failed building reqwest client"),
-            base_url,
-            sender,
-            api_key,
-        }
-    }
-    pub async fn send_email(
-        &self,
-        recipient: SubscriberEmail,
-        subject: &str,
-        html_content: &str,
-        text_content: &str,
-    ) -> Result<()> {
-        let url = format!("{}/email/send", self.base_url);
-        let send_email_form = SendEmailForm {
-            apikey: self.api_key.expose_secret(), // Should probably implement SerializableSecret on a custom type instead
-            subject,
-            from: self.sender.as_ref(),
-            to: recipient.as_ref(),
-            body_html: html_content,
-            body_text: text_content,
-            is_transactional: true,
-        };
-        let builder = self
-            .http_client
-            .post(&url)
-            .form(&send_email_form)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(())
-    }
-}
-
-#[derive(Serialize)]
-#[serde(rename_all = "PascalCase")]
-struct SendEmailForm<'a> {
-    apikey: &'a str,
-    subject: &'a str,
-    from: &'a str,
-    to: &'a str,
-    body_html: &'a str,
-    body_text: &'a str,
-    is_transactional: bool,
-}
-
-#[cfg(test)]
-mod tests {
-    use claims::{assert_err, assert_ok};
-    use fake::{
-        faker::{
-            internet::en::SafeEmail,
-            lorem::en::{Paragraph, Sentence},
-        },
-        Fake, Faker,
-    };
-    use secrecy::Secret;
-    use wiremock::{
-        matchers::{any, header, method, path},
-        Mock, MockServer, ResponseTemplate,
-    };
-
-    use crate::{domain::SubscriberEmail, email_client::EmailClient};
-
-    fn subject() -> String {
-        Sentence(1..2).fake()
-    }
-    fn content() -> String {
-        Paragraph(1..10).fake()
-    }
-    fn email() -> SubscriberEmail {
-        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
-    }
-
-    fn email_client(base_url: String) -> EmailClient {
-        EmailClient::new(
-            base_url,
-            email(),
-            Secret::new(Faker.fake()),
-            std::time::Duration::from_millis(200),
-        )
-    }
-
-    #[tokio::test]
-    async fn send_email_sends_the_expected_request() {
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        Mock::given(header("Content-Type", "application/x-www-form-urlencoded"))
-            .and(path("/email/send"))
-            .and(method("POST"))
-            .respond_with(ResponseTemplate::new(200))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        let _ = email_client
-            .send_email(email(), &subject(), &content(), &content())
-            .await;
-
-        // Assertions automatically done by mock
-    }
-
-    #[tokio::test]
-    async fn send_email_suceeds_if_the_server_returns_200() {
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        Mock::given(any())
-            .respond_with(ResponseTemplate::new(200))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        let outcome = email_client
-            .send_email(email(), &subject(), &content(), &content())
-            .await;
-
-        // Assertions automatically done by mock
-        assert_ok!(outcome);
-    }
-
-    #[tokio::test]
-    async fn send_email_fails_if_the_server_returns_500() {
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        Mock::given(any())
-            .respond_with(ResponseTemplate::new(500))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        let outcome = email_client
-            .send_email(email(), &subject(), &content(), &content())
-            .await;
-
-        // Assertions automatically done by mock
-        assert_err!(outcome);
-    }
-
-    #[tokio::test]
-    async fn send_email_times_out_if_the_server_takes_too_long() {
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        let response = ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(180));
-        Mock::given(any())
-            .respond_with(response)
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        let outcome = email_client
-            .send_email(email(), &subject(), &content(), &content())
-            .await;
-
-        // Assertions automatically done by mock
-        assert_err!(outcome);
-    }
-}
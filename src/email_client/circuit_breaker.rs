@@ -0,0 +1,161 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Sends go through.
+    Closed,
+    /// A single send is let through to find out whether the provider has recovered.
+    HalfOpen,
+    /// Sends are skipped until the open period is over.
+    Open,
+}
+
+impl CircuitState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "closed",
+            CircuitState::HalfOpen => "half_open",
+            CircuitState::Open => "open",
+        }
+    }
+}
+
+#[derive(Debug)]
+enum State {
+    Closed { consecutive_failures: u32 },
+    Open { until: Instant },
+    HalfOpen { trial_deadline: Instant },
+}
+
+/// Stops sending to a provider after `failure_threshold` consecutive failures.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    open_duration: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, open_duration: Duration) -> Self {
+        Self {
+            failure_threshold,
+            open_duration,
+            state: Mutex::new(State::Closed {
+                consecutive_failures: 0,
+            }),
+        }
+    }
+
+    /// Whether a send may go through, moving an expired open circuit to half open.
+    /// A half open trial that never reports back is abandoned after `open_duration`.
+    pub fn try_acquire(&self) -> bool {
+        let mut state = self.state.lock().expect("Circuit breaker lock poisoned");
+        let now = Instant::now();
+        match *state {
+            State::Closed { .. } => true,
+            State::Open { until }
+            | State::HalfOpen {
+                trial_deadline: until,
+            } if now >= until => {
+                *state = State::HalfOpen {
+                    trial_deadline: now + self.open_duration,
+                };
+                true
+            }
+            State::Open { .. } | State::HalfOpen { .. } => false,
+        }
+    }
+
+    pub fn record_success(&self) {
+        *self.state.lock().expect("Circuit breaker lock poisoned") = State::Closed {
+            consecutive_failures: 0,
+        };
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().expect("Circuit breaker lock poisoned");
+        let consecutive_failures = match *state {
+            State::Closed {
+                consecutive_failures,
+            } => consecutive_failures + 1,
+            // The trial failed, the provider is still down
+            State::HalfOpen { .. } | State::Open { .. } => self.failure_threshold,
+        };
+        *state = if consecutive_failures >= self.failure_threshold {
+            State::Open {
+                until: Instant::now() + self.open_duration,
+            }
+        } else {
+            State::Closed {
+                consecutive_failures,
+            }
+        };
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match *self.state.lock().expect("Circuit breaker lock poisoned") {
+            State::Closed { .. } => CircuitState::Closed,
+            State::Open { .. } => CircuitState::Open,
+            State::HalfOpen { .. } => CircuitState::HalfOpen,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::email_client::{CircuitBreaker, CircuitState};
+
+    #[test]
+    fn circuit_opens_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(60));
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.try_acquire());
+        breaker.record_failure();
+
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.try_acquire());
+    }
+
+    #[test]
+    fn a_success_resets_the_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn only_one_trial_is_let_through_once_the_open_period_is_over() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(10));
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(breaker.try_acquire());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert!(!breaker.try_acquire());
+    }
+
+    #[test]
+    fn a_failed_trial_opens_the_circuit_again() {
+        let breaker = CircuitBreaker::new(3, Duration::from_millis(10));
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(breaker.try_acquire());
+
+        breaker.record_failure();
+
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+}
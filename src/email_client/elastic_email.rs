@@ -0,0 +1,690 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use reqwest::{
+    header::HeaderMap,
+    multipart::{Form, Part},
+    Client, StatusCode,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    domain::SubscriberEmail,
+    email_client::{
+        Attachment, DeliveryReceipt, EmailMessage, EmailSender, RecipientOutcome, RetryPolicy,
+        SendEmailError, Sender,
+    },
+};
+
+/// Sends email through Elastic Email's `/email/send` form API.
+pub struct ElasticEmailClient {
+    http_client: Client,
+    base_url: String,
+    sender: Sender,
+    api_key: Secret<String>,
+    retry_policy: RetryPolicy,
+    batch_size: usize,
+}
+
+impl ElasticEmailClient {
+    pub fn new(
+        base_url: String,
+        sender: impl Into<Sender>,
+        api_key: Secret<String>,
+        timeout: std::time::Duration,
+        retry_policy: RetryPolicy,
+        batch_size: usize,
+    ) -> Self {
+        Self {
+            http_client: Client::builder()
+                .timeout(timeout)
+                .build()
+                .expect("Failed building reqwest client"),
+            base_url,
+            sender: sender.into(),
+            api_key,
+            retry_policy,
+            batch_size: batch_size.max(1),
+        }
+    }
+
+    /// Elastic Email has a single channel per email, tags are joined into it.
+    /// Custom headers are passed as `headers_<name>` fields holding the whole header line.
+    fn form<'a>(&'a self, message: &'a EmailMessage) -> SendEmailForm<'a> {
+        SendEmailForm {
+            apikey: self.api_key.expose_secret(), // Should probably implement SerializableSecret on a custom type instead
+            subject: &message.subject,
+            from: self.sender.email.as_ref(),
+            from_name: self.sender.name.as_deref(),
+            to: None,
+            msg_to: None,
+            reply_to: message.reply_to.as_ref().map(|reply_to| reply_to.as_ref()),
+            body_html: &message.html_content,
+            body_text: &message.text_content,
+            channel: (!message.tags.is_empty()).then(|| message.tags.join(",")),
+            is_transactional: true,
+            headers: message
+                .headers()
+                .iter()
+                .map(|(name, value)| (format!("headers_{name}"), format!("{name}: {value}")))
+                .collect(),
+        }
+    }
+
+    async fn send_once(
+        &self,
+        url: &str,
+        form: &SendEmailForm<'_>,
+        attachments: &[Attachment],
+    ) -> Result<DeliveryReceipt, SendEmailError> {
+        let request = self.http_client.post(url);
+        // Attachments have to be uploaded as files, which needs a multipart body
+        let request = if attachments.is_empty() {
+            request.form(form)
+        } else {
+            request.multipart(multipart_form(form, attachments)?)
+        };
+        let response = request.send().await.map_err(|e| {
+            if e.is_builder() {
+                SendEmailError::Unexpected(e.into())
+            } else {
+                // Timeouts and connection failures
+                SendEmailError::Transient {
+                    status: None,
+                    body: e.to_string(),
+                    retry_after: None,
+                }
+            }
+        })?;
+        let status = response.status();
+        let retry_after = retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        if status.is_success() {
+            return parse_response(&body);
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::REQUEST_TIMEOUT
+            || status.is_server_error()
+        {
+            Err(SendEmailError::Transient {
+                status: Some(status.as_u16()),
+                body,
+                retry_after,
+            })
+        } else {
+            Err(SendEmailError::Permanent {
+                status: Some(status.as_u16()),
+                body,
+            })
+        }
+    }
+}
+
+/// Inline images are uploaded like attachments, named after their content id.
+fn multipart_form(
+    form: &SendEmailForm<'_>,
+    attachments: &[Attachment],
+) -> Result<Form, SendEmailError> {
+    let fields = match serde_json::to_value(form).context("Failed to serialize email")? {
+        serde_json::Value::Object(fields) => fields,
+        _ => unreachable!("SendEmailForm serializes to an object"),
+    };
+    let mut multipart = Form::new();
+    for (name, value) in fields {
+        let value = match value {
+            serde_json::Value::String(value) => value,
+            value => value.to_string(),
+        };
+        multipart = multipart.text(name, value);
+    }
+    for (i, attachment) in attachments.iter().enumerate() {
+        let part = Part::bytes(attachment.content.clone())
+            .file_name(attachment.filename.clone())
+            .mime_str(&attachment.content_type)
+            .with_context(|| format!("Invalid content type {}", attachment.content_type))?;
+        multipart = multipart.part(format!("attachment{i}"), part);
+    }
+    Ok(multipart)
+}
+
+/// Elastic Email answers most failures with a 200 and `success: false`.
+/// A response we can't make sense of isn't retried, the email may well have been sent.
+fn parse_response(body: &str) -> Result<DeliveryReceipt, SendEmailError> {
+    let response: SendEmailResponse = serde_json::from_str(body).map_err(|e| {
+        SendEmailError::Unexpected(
+            anyhow!(e).context(format!("Failed to parse Elastic Email response: {body}")),
+        )
+    })?;
+    if !response.success {
+        return Err(SendEmailError::Permanent {
+            status: Some(StatusCode::OK.as_u16()),
+            body: response.error.unwrap_or_else(|| body.to_string()),
+        });
+    }
+    let data = response.data.unwrap_or_default();
+    Ok(DeliveryReceipt {
+        message_id: data.messageid,
+        transaction_id: data.transactionid,
+    })
+}
+
+/// Only the delay-seconds form of `Retry-After`, which is what rate limiting APIs send.
+fn retry_after(headers: &HeaderMap) -> Option<std::time::Duration> {
+    let seconds = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(std::time::Duration::from_secs(seconds))
+}
+
+#[async_trait]
+impl EmailSender for ElasticEmailClient {
+    async fn send_email(
+        &self,
+        recipient: SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<DeliveryReceipt, SendEmailError> {
+        let url = format!("{}/email/send", self.base_url);
+        let send_email_form = SendEmailForm {
+            to: Some(recipient.as_ref()),
+            ..self.form(message)
+        };
+        self.retry_policy
+            .run(|| self.send_once(&url, &send_email_form, &message.attachments))
+            .await
+    }
+
+    /// Uses `msgTo`, which sends a separate copy to each recipient of a chunk.
+    /// Elastic Email returns a single transaction id for the whole chunk.
+    /// A chunk rejected outright is sent again one recipient at a time, so a single
+    /// bad address doesn't fail everyone it was batched with.
+    async fn send_batch(
+        &self,
+        recipients: &[SubscriberEmail],
+        message: &EmailMessage,
+    ) -> Vec<RecipientOutcome> {
+        let url = format!("{}/email/send", self.base_url);
+        let mut outcomes = Vec::with_capacity(recipients.len());
+        for chunk in recipients.chunks(self.batch_size) {
+            let msg_to = chunk
+                .iter()
+                .map(|recipient| recipient.as_ref())
+                .collect::<Vec<_>>()
+                .join(",");
+            let send_email_form = SendEmailForm {
+                msg_to: Some(&msg_to),
+                ..self.form(message)
+            };
+            let outcome = self
+                .retry_policy
+                .run(|| self.send_once(&url, &send_email_form, &message.attachments))
+                .await;
+            if chunk.len() > 1 && matches!(outcome, Err(SendEmailError::Permanent { .. })) {
+                for recipient in chunk {
+                    outcomes.push(RecipientOutcome {
+                        recipient: recipient.clone(),
+                        outcome: self.send_email(recipient.clone(), message).await,
+                    });
+                }
+                continue;
+            }
+            outcomes.extend(chunk.iter().map(|recipient| RecipientOutcome {
+                recipient: recipient.clone(),
+                outcome: match &outcome {
+                    Ok(receipt) => Ok(DeliveryReceipt {
+                        message_id: None,
+                        transaction_id: receipt.transaction_id.clone(),
+                    }),
+                    Err(e) => Err(e.duplicate()),
+                },
+            }));
+        }
+        outcomes
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SendEmailForm<'a> {
+    apikey: &'a str,
+    subject: &'a str,
+    from: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    from_name: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    to: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    msg_to: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<&'a str>,
+    body_html: &'a str,
+    body_text: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    channel: Option<String>,
+    is_transactional: bool,
+    #[serde(flatten)]
+    headers: BTreeMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct SendEmailResponse {
+    success: bool,
+    error: Option<String>,
+    data: Option<SendEmailResponseData>,
+}
+
+#[derive(Deserialize, Default)]
+struct SendEmailResponseData {
+    transactionid: Option<String>,
+    messageid: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_ok;
+    use fake::{
+        faker::{
+            internet::en::SafeEmail,
+            lorem::en::{Paragraph, Sentence},
+        },
+        Fake, Faker,
+    };
+    use secrecy::Secret;
+    use wiremock::{
+        matchers::{any, body_string_contains, header, header_regex, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use crate::{
+        domain::SubscriberEmail,
+        email_client::{
+            ElasticEmailClient, EmailMessage, EmailSender, RetryPolicy, SendEmailError, Sender,
+        },
+    };
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+    fn message() -> EmailMessage {
+        EmailMessage::new(subject(), content(), content())
+    }
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    fn success() -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "success": true,
+            "data": {"transactionid": "transaction-id", "messageid": "message-id"}
+        }))
+    }
+
+    fn email_client(base_url: String) -> ElasticEmailClient {
+        ElasticEmailClient::new(
+            base_url,
+            email(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            RetryPolicy {
+                max_attempts: 3,
+                base_delay: std::time::Duration::from_millis(10),
+                max_delay: std::time::Duration::from_secs(2),
+            },
+            2,
+        )
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_the_expected_request() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header("Content-Type", "application/x-www-form-urlencoded"))
+            .and(path("/email/send"))
+            .and(method("POST"))
+            .respond_with(success())
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let _ = email_client.send_email(email(), &message()).await;
+
+        // Assertions automatically done by mock
+    }
+
+    #[tokio::test]
+    async fn send_email_passes_on_the_message_details() {
+        let mock_server = MockServer::start().await;
+        let email_client = ElasticEmailClient::new(
+            mock_server.uri(),
+            Sender {
+                email: SubscriberEmail::parse("news@example.com".into()).unwrap(),
+                name: Some("Newsletter".into()),
+            },
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            RetryPolicy {
+                max_attempts: 1,
+                base_delay: std::time::Duration::ZERO,
+                max_delay: std::time::Duration::ZERO,
+            },
+            2,
+        );
+
+        Mock::given(header("Content-Type", "application/x-www-form-urlencoded"))
+            .and(body_string_contains("FromName=Newsletter"))
+            .and(body_string_contains("ReplyTo=help%40example.com"))
+            .and(body_string_contains("Channel=welcome%2Conboarding"))
+            .and(body_string_contains(
+                "headers_X-Campaign=X-Campaign%3A+spring",
+            ))
+            .respond_with(success())
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let message = message()
+            .reply_to(SubscriberEmail::parse("help@example.com".into()).unwrap())
+            .tag("welcome")
+            .tag("onboarding")
+            .header("X-Campaign", "spring")
+            .unwrap();
+        let outcome = email_client.send_email(email(), &message).await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn attachments_are_uploaded_as_files() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_regex("Content-Type", "^multipart/form-data"))
+            .and(body_string_contains(r#"filename="invoice.pdf""#))
+            .and(body_string_contains(r#"filename="logo""#))
+            .and(body_string_contains("%PDF-1.4"))
+            .and(body_string_contains(r#"name="IsTransactional""#))
+            .respond_with(success())
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let message = message()
+            .attachment("invoice.pdf", "application/pdf", b"%PDF-1.4".to_vec())
+            .inline_image("logo", "image/png", b"PNG".to_vec());
+        let outcome = email_client.send_email(email(), &message).await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_suceeds_if_the_server_returns_200() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(success())
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(email(), &message()).await;
+
+        // Assertions automatically done by mock
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_returns_the_provider_ids() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(success())
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let receipt = email_client.send_email(email(), &message()).await.unwrap();
+
+        assert_eq!(receipt.transaction_id.as_deref(), Some("transaction-id"));
+        assert_eq!(receipt.message_id.as_deref(), Some("message-id"));
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_permanently_if_the_provider_reports_failure_with_a_200() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "success": false,
+                "error": "Not enough credit"
+            })))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(email(), &message()).await;
+
+        match outcome {
+            Err(SendEmailError::Permanent { status, body }) => {
+                assert_eq!(status, Some(200));
+                assert_eq!(body, "Not enough credit");
+            }
+            _ => panic!("Expected a permanent failure, got {outcome:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_without_retrying_if_the_response_is_not_json() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_body_string("OK"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(email(), &message()).await;
+
+        assert!(matches!(outcome, Err(SendEmailError::Unexpected(_))));
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_keeps_returning_500() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500).set_body_string("Internal error"))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(email(), &message()).await;
+
+        // Assertions automatically done by mock
+        match outcome {
+            Err(SendEmailError::Transient { status, body, .. }) => {
+                assert_eq!(status, Some(500));
+                assert_eq!(body, "Internal error");
+            }
+            _ => panic!("Expected a transient failure, got {outcome:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email_retries_after_a_server_error() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(success())
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(email(), &message()).await;
+
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_waits_for_retry_after_when_rate_limited() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(success())
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let start = std::time::Instant::now();
+        let outcome = email_client.send_email(email(), &message()).await;
+
+        assert_ok!(outcome);
+        assert!(start.elapsed() >= std::time::Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_permanently_without_retrying_if_the_server_returns_400() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(400).set_body_string("Invalid recipient"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(email(), &message()).await;
+
+        match outcome {
+            Err(SendEmailError::Permanent { status, body }) => {
+                assert_eq!(status, Some(400));
+                assert_eq!(body, "Invalid recipient");
+            }
+            _ => panic!("Expected a permanent failure, got {outcome:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn send_batch_sends_recipients_in_chunks() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let recipients: Vec<_> = (0..5).map(|_| email()).collect();
+
+        Mock::given(path("/email/send"))
+            .and(body_string_contains("MsgTo="))
+            .respond_with(success())
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let outcomes = email_client.send_batch(&recipients, &message()).await;
+
+        assert_eq!(outcomes.len(), 5);
+        for (outcome, recipient) in outcomes.iter().zip(&recipients) {
+            assert_eq!(outcome.recipient.as_ref(), recipient.as_ref());
+            let receipt = outcome.outcome.as_ref().unwrap();
+            assert_eq!(receipt.transaction_id.as_deref(), Some("transaction-id"));
+        }
+    }
+
+    #[tokio::test]
+    async fn send_batch_sends_a_rejected_chunk_one_recipient_at_a_time() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let recipients: Vec<_> = ["first@example.com", "bad@example.com", "last@example.com"]
+            .into_iter()
+            .map(|email| SubscriberEmail::parse(email.into()).unwrap())
+            .collect();
+
+        Mock::given(body_string_contains("bad%40example.com"))
+            .respond_with(ResponseTemplate::new(400).set_body_string("Invalid recipient"))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(success())
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let outcomes = email_client.send_batch(&recipients, &message()).await;
+
+        assert_eq!(outcomes.len(), 3);
+        assert_ok!(&outcomes[0].outcome);
+        assert!(matches!(
+            outcomes[1].outcome,
+            Err(SendEmailError::Permanent { .. })
+        ));
+        assert_ok!(&outcomes[2].outcome);
+    }
+
+    #[tokio::test]
+    async fn send_batch_does_not_resend_a_chunk_that_failed_transiently() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let recipients: Vec<_> = (0..2).map(|_| email()).collect();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let outcomes = email_client.send_batch(&recipients, &message()).await;
+
+        for outcome in &outcomes {
+            assert!(matches!(
+                outcome.outcome,
+                Err(SendEmailError::Transient { .. })
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        let response = success().set_delay(std::time::Duration::from_secs(180));
+        Mock::given(any())
+            .respond_with(response)
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client.send_email(email(), &message()).await;
+
+        // Assertions automatically done by mock
+        assert!(matches!(
+            outcome,
+            Err(SendEmailError::Transient { status: None, .. })
+        ));
+    }
+}
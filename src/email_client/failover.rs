@@ -0,0 +1,387 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    configuration::CircuitBreakerSettings,
+    domain::SubscriberEmail,
+    email_client::{
+        CircuitBreaker, CircuitState, DeliveryReceipt, EmailMessage, EmailSender, RecipientOutcome,
+        SendEmailError,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub struct ProviderHealth {
+    /// Position in the failover chain, the primary is 0.
+    pub position: usize,
+    pub provider: String,
+    pub state: CircuitState,
+}
+
+struct Provider {
+    name: String,
+    sender: Arc<dyn EmailSender>,
+    breaker: CircuitBreaker,
+}
+
+/// Tries each provider in order, skipping those whose circuit is open.
+///
+/// Only transient failures move on to the next provider. A permanent failure would
+/// be rejected everywhere, and after an unexpected one the email may have been sent.
+pub struct FailoverEmailSender {
+    providers: Vec<Provider>,
+}
+
+impl FailoverEmailSender {
+    pub fn new(
+        providers: Vec<(String, Arc<dyn EmailSender>)>,
+        settings: &CircuitBreakerSettings,
+    ) -> Self {
+        Self {
+            providers: providers
+                .into_iter()
+                .map(|(name, sender)| Provider {
+                    name,
+                    sender,
+                    breaker: CircuitBreaker::new(
+                        settings.failure_threshold,
+                        settings.open_duration(),
+                    ),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailSender for FailoverEmailSender {
+    async fn send_email(
+        &self,
+        recipient: SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<DeliveryReceipt, SendEmailError> {
+        let mut last_error = None;
+        for provider in &self.providers {
+            if !provider.breaker.try_acquire() {
+                continue;
+            }
+            match provider.sender.send_email(recipient.clone(), message).await {
+                Ok(receipt) => {
+                    provider.breaker.record_success();
+                    return Ok(receipt);
+                }
+                Err(e @ SendEmailError::Permanent { .. }) => {
+                    // The provider is up, it just didn't like this email
+                    provider.breaker.record_success();
+                    return Err(e);
+                }
+                Err(e @ SendEmailError::Transient { .. }) => {
+                    provider.breaker.record_failure();
+                    tracing::warn!("Email provider {} failed, failing over: {e}", provider.name);
+                    last_error = Some(e);
+                }
+                Err(e) => {
+                    provider.breaker.record_failure();
+                    return Err(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(every_circuit_open))
+    }
+
+    /// Recipients whose send failed transiently are passed on to the next provider.
+    async fn send_batch(
+        &self,
+        recipients: &[SubscriberEmail],
+        message: &EmailMessage,
+    ) -> Vec<RecipientOutcome> {
+        let mut outcomes: Vec<Option<RecipientOutcome>> = recipients.iter().map(|_| None).collect();
+        let mut pending: Vec<usize> = (0..recipients.len()).collect();
+        for provider in &self.providers {
+            if pending.is_empty() {
+                break;
+            }
+            if !provider.breaker.try_acquire() {
+                continue;
+            }
+            let batch: Vec<SubscriberEmail> =
+                pending.iter().map(|&i| recipients[i].clone()).collect();
+            let batch_outcomes = provider.sender.send_batch(&batch, message).await;
+
+            let mut provider_responded = false;
+            let mut still_pending = Vec::new();
+            for (i, outcome) in pending.into_iter().zip(batch_outcomes) {
+                match outcome.outcome {
+                    Ok(_) | Err(SendEmailError::Permanent { .. }) => provider_responded = true,
+                    Err(SendEmailError::Transient { .. }) => still_pending.push(i),
+                    Err(SendEmailError::Suppressed(_) | SendEmailError::Unexpected(_)) => {}
+                }
+                outcomes[i] = Some(outcome);
+            }
+            if provider_responded {
+                provider.breaker.record_success();
+            } else {
+                provider.breaker.record_failure();
+            }
+            if !still_pending.is_empty() {
+                tracing::warn!(
+                    "Email provider {} failed for {} recipients, failing over",
+                    provider.name,
+                    still_pending.len()
+                );
+            }
+            pending = still_pending;
+        }
+        outcomes
+            .into_iter()
+            .zip(recipients)
+            .map(|(outcome, recipient)| {
+                outcome.unwrap_or_else(|| RecipientOutcome {
+                    recipient: recipient.clone(),
+                    outcome: Err(every_circuit_open()),
+                })
+            })
+            .collect()
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        self.providers
+            .iter()
+            .enumerate()
+            .map(|(position, provider)| ProviderHealth {
+                position,
+                provider: provider.name.clone(),
+                state: provider.breaker.state(),
+            })
+            .collect()
+    }
+}
+
+fn every_circuit_open() -> SendEmailError {
+    SendEmailError::Transient {
+        status: None,
+        body: "Every email provider's circuit is open".into(),
+        retry_after: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use claims::assert_ok;
+    use fake::{faker::internet::en::SafeEmail, Fake, Faker};
+    use secrecy::Secret;
+    use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+
+    use crate::{
+        configuration::CircuitBreakerSettings,
+        domain::SubscriberEmail,
+        email_client::{
+            CircuitState, ElasticEmailClient, EmailMessage, EmailSender, FailoverEmailSender,
+            RetryPolicy, SendEmailError,
+        },
+    };
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    fn success() -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({"success": true}))
+    }
+
+    fn elastic_email_client(mock_server: &MockServer) -> Arc<dyn EmailSender> {
+        Arc::new(ElasticEmailClient::new(
+            mock_server.uri(),
+            email(),
+            Secret::new(Faker.fake()),
+            Duration::from_millis(200),
+            RetryPolicy {
+                max_attempts: 1,
+                base_delay: Duration::ZERO,
+                max_delay: Duration::ZERO,
+            },
+            50,
+        ))
+    }
+
+    fn failover(
+        primary: &MockServer,
+        secondary: &MockServer,
+        open_seconds: u64,
+    ) -> FailoverEmailSender {
+        FailoverEmailSender::new(
+            vec![
+                ("primary".into(), elastic_email_client(primary)),
+                ("secondary".into(), elastic_email_client(secondary)),
+            ],
+            &CircuitBreakerSettings {
+                failure_threshold: 2,
+                open_seconds,
+            },
+        )
+    }
+
+    async fn send(email_sender: &FailoverEmailSender) -> Result<(), SendEmailError> {
+        email_sender
+            .send_email(email(), &EmailMessage::new("subject", "html", "text"))
+            .await
+            .map(|_| ())
+    }
+
+    #[tokio::test]
+    async fn emails_go_to_the_secondary_while_the_primary_fails() {
+        let primary = MockServer::start().await;
+        let secondary = MockServer::start().await;
+        let email_sender = failover(&primary, &secondary, 60);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&primary)
+            .await;
+        Mock::given(any())
+            .respond_with(success())
+            .expect(1)
+            .mount(&secondary)
+            .await;
+
+        assert_ok!(send(&email_sender).await);
+    }
+
+    #[tokio::test]
+    async fn an_open_circuit_skips_the_primary_without_waiting_for_it() {
+        let primary = MockServer::start().await;
+        let secondary = MockServer::start().await;
+        let email_sender = failover(&primary, &secondary, 60);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            // Only until the circuit opens
+            .expect(2)
+            .mount(&primary)
+            .await;
+        Mock::given(any())
+            .respond_with(success())
+            .expect(4)
+            .mount(&secondary)
+            .await;
+
+        for _ in 0..4 {
+            assert_ok!(send(&email_sender).await);
+        }
+        let health = email_sender.health();
+        assert_eq!(health[0].state, CircuitState::Open);
+        assert_eq!(health[1].state, CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn the_primary_is_used_again_once_it_recovers() {
+        let primary = MockServer::start().await;
+        let secondary = MockServer::start().await;
+        let email_sender = failover(&primary, &secondary, 1);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&primary)
+            .await;
+        Mock::given(any())
+            .respond_with(success())
+            .expect(1)
+            .mount(&primary)
+            .await;
+        Mock::given(any())
+            .respond_with(success())
+            .expect(2)
+            .mount(&secondary)
+            .await;
+
+        assert_ok!(send(&email_sender).await);
+        assert_ok!(send(&email_sender).await);
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert_ok!(send(&email_sender).await);
+
+        assert_eq!(email_sender.health()[0].state, CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn permanent_failures_are_not_sent_to_the_secondary() {
+        let primary = MockServer::start().await;
+        let secondary = MockServer::start().await;
+        let email_sender = failover(&primary, &secondary, 60);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(400))
+            .expect(1)
+            .mount(&primary)
+            .await;
+        Mock::given(any())
+            .respond_with(success())
+            .expect(0)
+            .mount(&secondary)
+            .await;
+
+        let outcome = send(&email_sender).await;
+
+        assert!(matches!(outcome, Err(SendEmailError::Permanent { .. })));
+        assert_eq!(email_sender.health()[0].state, CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn batches_fail_over_to_the_secondary() {
+        let primary = MockServer::start().await;
+        let secondary = MockServer::start().await;
+        let email_sender = failover(&primary, &secondary, 60);
+        let recipients: Vec<_> = (0..3).map(|_| email()).collect();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(1)
+            .mount(&primary)
+            .await;
+        Mock::given(any())
+            .respond_with(success())
+            .expect(1)
+            .mount(&secondary)
+            .await;
+
+        let outcomes = email_sender
+            .send_batch(&recipients, &EmailMessage::new("subject", "html", "text"))
+            .await;
+
+        assert_eq!(outcomes.len(), 3);
+        for (outcome, recipient) in outcomes.iter().zip(&recipients) {
+            assert_eq!(outcome.recipient.as_ref(), recipient.as_ref());
+            assert_ok!(&outcome.outcome);
+        }
+    }
+
+    #[tokio::test]
+    async fn sending_fails_when_every_provider_is_down() {
+        let primary = MockServer::start().await;
+        let secondary = MockServer::start().await;
+        let email_sender = failover(&primary, &secondary, 60);
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&primary)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .mount(&secondary)
+            .await;
+
+        for _ in 0..3 {
+            let outcome = send(&email_sender).await;
+            assert!(matches!(outcome, Err(SendEmailError::Transient { .. })));
+        }
+        assert!(email_sender
+            .health()
+            .iter()
+            .all(|provider| provider.state == CircuitState::Open));
+    }
+}
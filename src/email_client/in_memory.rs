@@ -0,0 +1,51 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+
+use crate::{
+    domain::SubscriberEmail,
+    email_client::{DeliveryReceipt, EmailMessage, EmailSender, SendEmailError},
+};
+
+#[derive(Debug, Clone)]
+pub struct RecordedEmail {
+    pub recipient: String,
+    pub message: EmailMessage,
+}
+
+/// Records emails instead of sending them, for tests.
+#[derive(Default)]
+pub struct InMemoryEmailSender {
+    sent: Mutex<Vec<RecordedEmail>>,
+}
+
+impl InMemoryEmailSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sent_emails(&self) -> Vec<RecordedEmail> {
+        self.sent
+            .lock()
+            .expect("Email recorder lock poisoned")
+            .clone()
+    }
+}
+
+#[async_trait]
+impl EmailSender for InMemoryEmailSender {
+    async fn send_email(
+        &self,
+        recipient: SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<DeliveryReceipt, SendEmailError> {
+        self.sent
+            .lock()
+            .expect("Email recorder lock poisoned")
+            .push(RecordedEmail {
+                recipient: recipient.as_ref().to_string(),
+                message: message.clone(),
+            });
+        Ok(DeliveryReceipt::default())
+    }
+}
@@ -0,0 +1,171 @@
+use anyhow::{bail, Result};
+
+use crate::domain::SubscriberEmail;
+
+/// Headers the providers set themselves. A second copy would either be dropped
+/// or produce a message receivers treat as malformed, so they can't be custom headers.
+const RESERVED_HEADERS: &[&str] = &[
+    "From",
+    "To",
+    "Subject",
+    "Reply-To",
+    "Message-ID",
+    "MIME-Version",
+    "Date",
+    "DKIM-Signature",
+    "Content-Type",
+];
+
+/// The address emails are sent from, with an optional display name.
+#[derive(Debug, Clone)]
+pub struct Sender {
+    pub email: SubscriberEmail,
+    pub name: Option<String>,
+}
+
+impl From<SubscriberEmail> for Sender {
+    fn from(email: SubscriberEmail) -> Self {
+        Self { email, name: None }
+    }
+}
+
+/// A file sent along with an email. Attachments with a `content_id` are shown
+/// inline, referenced from the HTML body as `cid:<content_id>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+    pub content_id: Option<String>,
+}
+
+/// Everything about an email except who it is sent to.
+#[derive(Debug, Clone, Default)]
+pub struct EmailMessage {
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    pub reply_to: Option<SubscriberEmail>,
+    pub attachments: Vec<Attachment>,
+    /// Categories for the provider's statistics, where supported.
+    pub tags: Vec<String>,
+    /// Only added through `header`, which checks them.
+    headers: Vec<(String, String)>,
+}
+
+impl EmailMessage {
+    pub fn new(
+        subject: impl Into<String>,
+        html_content: impl Into<String>,
+        text_content: impl Into<String>,
+    ) -> Self {
+        Self {
+            subject: subject.into(),
+            html_content: html_content.into(),
+            text_content: text_content.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn reply_to(mut self, reply_to: SubscriberEmail) -> Self {
+        self.reply_to = Some(reply_to);
+        self
+    }
+
+    pub fn attachment(
+        mut self,
+        filename: impl Into<String>,
+        content_type: impl Into<String>,
+        content: Vec<u8>,
+    ) -> Self {
+        self.attachments.push(Attachment {
+            filename: filename.into(),
+            content_type: content_type.into(),
+            content,
+            content_id: None,
+        });
+        self
+    }
+
+    pub fn inline_image(
+        mut self,
+        content_id: impl Into<String>,
+        content_type: impl Into<String>,
+        content: Vec<u8>,
+    ) -> Self {
+        let content_id = content_id.into();
+        self.attachments.push(Attachment {
+            filename: content_id.clone(),
+            content_type: content_type.into(),
+            content,
+            content_id: Some(content_id),
+        });
+        self
+    }
+
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    /// Adds a custom header. Headers the providers set themselves and malformed ones are
+    /// rejected here, as they are a mistake of the caller rather than something a
+    /// provider should be blamed for when sending.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Result<Self> {
+        let (name, value) = (name.into(), value.into());
+        if name.is_empty() || !name.bytes().all(|b| b.is_ascii_graphic() && b != b':') {
+            bail!("{name:?} is not a valid header name");
+        }
+        if value.contains(['\r', '\n']) {
+            bail!("The value of the {name} header spans several lines");
+        }
+        if RESERVED_HEADERS
+            .iter()
+            .any(|reserved| reserved.eq_ignore_ascii_case(&name))
+        {
+            bail!("{name} is set by the email client and can't be a custom header");
+        }
+        self.headers.push((name, value));
+        Ok(self)
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::EmailMessage;
+
+    #[test]
+    fn custom_headers_are_accepted() {
+        let message =
+            assert_ok!(EmailMessage::default().header("List-Unsubscribe", "<https://example.com>"));
+        assert_eq!(
+            message.headers(),
+            [("List-Unsubscribe".into(), "<https://example.com>".into())]
+        );
+    }
+
+    #[test]
+    fn headers_the_provider_sets_are_rejected_whatever_their_case() {
+        for name in ["From", "message-id", "DKIM-SIGNATURE", "Reply-To"] {
+            assert_err!(EmailMessage::default().header(name, "value"));
+        }
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        for (name, value) in [
+            ("", "value"),
+            ("X Campaign", "value"),
+            ("X-Campaign:", "value"),
+            ("X-Campaign", "spring\r\nBcc: everyone@example.com"),
+        ] {
+            assert_err!(EmailMessage::default().header(name, value));
+        }
+    }
+}
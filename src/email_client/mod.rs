@@ -0,0 +1,293 @@
+mod circuit_breaker;
+mod elastic_email;
+mod failover;
+mod in_memory;
+mod message;
+mod outbox;
+mod reloadable;
+mod retry;
+mod sandbox;
+mod smtp;
+
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+
+pub use circuit_breaker::{CircuitBreaker, CircuitState};
+pub use elastic_email::ElasticEmailClient;
+pub use failover::{FailoverEmailSender, ProviderHealth};
+pub use in_memory::{InMemoryEmailSender, RecordedEmail};
+pub use message::{Attachment, EmailMessage, Sender};
+pub use outbox::{outbox_directory, read_outbox, OutboxEmail, OutboxEmailSender};
+pub use reloadable::ReloadableEmailSender;
+pub use retry::RetryPolicy;
+pub use sandbox::{SandboxEmailSender, SandboxMode, ORIGINAL_RECIPIENT_HEADER};
+pub use smtp::SmtpEmailClient;
+
+use crate::{
+    configuration::{EmailClientSettings, EmailProvider, FallbackSettings, SandboxSettings},
+    domain::SubscriberEmail,
+};
+
+#[async_trait]
+pub trait EmailSender: Send + Sync {
+    async fn send_email(
+        &self,
+        recipient: SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<DeliveryReceipt, SendEmailError>;
+
+    /// Sends the same email to each recipient separately, returning one outcome per
+    /// recipient in the same order. Providers that can take several recipients per
+    /// call override this, the default sends them one at a time.
+    async fn send_batch(
+        &self,
+        recipients: &[SubscriberEmail],
+        message: &EmailMessage,
+    ) -> Vec<RecipientOutcome> {
+        let mut outcomes = Vec::with_capacity(recipients.len());
+        for recipient in recipients {
+            outcomes.push(RecipientOutcome {
+                recipient: recipient.clone(),
+                outcome: self.send_email(recipient.clone(), message).await,
+            });
+        }
+        outcomes
+    }
+
+    /// Circuit breaker state of the providers behind this sender, if it tracks any.
+    fn health(&self) -> Vec<ProviderHealth> {
+        Vec::new()
+    }
+}
+
+/// Identifiers the provider assigned to an accepted email, used to match it
+/// against the provider's later delivery notifications.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeliveryReceipt {
+    pub message_id: Option<String>,
+    pub transaction_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct RecipientOutcome {
+    pub recipient: SubscriberEmail,
+    pub outcome: Result<DeliveryReceipt, SendEmailError>,
+}
+
+impl RecipientOutcome {
+    /// For a recipient a wrapped sender's batch returned no outcome for.
+    pub(crate) fn missing(recipient: &SubscriberEmail) -> Self {
+        Self {
+            recipient: recipient.clone(),
+            outcome: Err(SendEmailError::Unexpected(anyhow::anyhow!(
+                "No outcome was returned for {}",
+                recipient.as_ref()
+            ))),
+        }
+    }
+}
+
+/// Why an email couldn't be sent, so callers can decide whether to try again later.
+#[derive(thiserror::Error, Debug)]
+pub enum SendEmailError {
+    /// Timeouts, rate limiting and server errors, worth retrying later.
+    #[error("Transient failure sending email{}", describe_response(*status, body))]
+    Transient {
+        status: Option<u16>,
+        body: String,
+        retry_after: Option<Duration>,
+    },
+    /// The provider rejected the email, retrying won't help.
+    #[error("Permanent failure sending email{}", describe_response(*status, body))]
+    Permanent { status: Option<u16>, body: String },
+    /// The recipient is on the suppression list, nothing was sent.
+    #[error("{0} is on the suppression list")]
+    Suppressed(String),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl SendEmailError {
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::Transient { .. })
+    }
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Transient { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// A copy for each recipient of a batch that failed as a whole.
+    /// Unexpected errors lose their source chain, keeping only the message.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            Self::Transient {
+                status,
+                body,
+                retry_after,
+            } => Self::Transient {
+                status: *status,
+                body: body.clone(),
+                retry_after: *retry_after,
+            },
+            Self::Permanent { status, body } => Self::Permanent {
+                status: *status,
+                body: body.clone(),
+            },
+            Self::Suppressed(recipient) => Self::Suppressed(recipient.clone()),
+            Self::Unexpected(e) => Self::Unexpected(anyhow::anyhow!("{e:#}")),
+        }
+    }
+}
+
+fn describe_response(status: Option<u16>, body: &str) -> String {
+    match (status, body.is_empty()) {
+        (Some(status), false) => format!(" ({status}): {body}"),
+        (Some(status), true) => format!(" ({status})"),
+        (None, false) => format!(": {body}"),
+        (None, true) => String::new(),
+    }
+}
+
+/// Builds the backend selected by `provider` in the email client settings,
+/// followed by its fallbacks, each behind its own circuit breaker, and all of them
+/// behind the sandbox if one is configured.
+///
+/// Only the last provider retries, the others fail over right away rather than
+/// holding every send up for the whole retry policy during an outage.
+pub fn build_email_sender(
+    config: &EmailClientSettings,
+    sender: Sender,
+) -> Result<Arc<dyn EmailSender>> {
+    let primary = FallbackSettings {
+        provider: config.provider,
+        base_url: None,
+        api_key: None,
+    };
+    let last = config.fallbacks.len();
+    let providers = std::iter::once(&primary)
+        .chain(&config.fallbacks)
+        .enumerate()
+        .map(|(position, provider)| {
+            let retry_policy = match position == last {
+                true => config.retry_policy(),
+                false => RetryPolicy {
+                    max_attempts: 1,
+                    ..config.retry_policy()
+                },
+            };
+            let email_sender = build_provider(config, provider, sender.clone(), retry_policy)?;
+            Ok((provider.provider.as_str().to_string(), email_sender))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let email_sender = Arc::new(FailoverEmailSender::new(providers, &config.circuit_breaker));
+    let mode = match &config.sandbox {
+        None => return Ok(email_sender),
+        Some(SandboxSettings::Redirect { redirect_to }) => SandboxMode::Redirect(
+            SubscriberEmail::parse(redirect_to.clone())
+                .context("Invalid sandbox redirect address")?,
+        ),
+        Some(SandboxSettings::Allowlist { allowed_domains }) => {
+            SandboxMode::Allowlist(allowed_domains.clone())
+        }
+        Some(SandboxSettings::Record) => SandboxMode::Record,
+    };
+    tracing::warn!("Email sandbox enabled: {mode:?}");
+    Ok(Arc::new(SandboxEmailSender::new(email_sender, mode)))
+}
+
+fn build_provider(
+    config: &EmailClientSettings,
+    provider: &FallbackSettings,
+    sender: Sender,
+    retry_policy: RetryPolicy,
+) -> Result<Arc<dyn EmailSender>> {
+    let email_sender: Arc<dyn EmailSender> = match provider.provider {
+        EmailProvider::ElasticEmail => Arc::new(ElasticEmailClient::new(
+            provider
+                .base_url
+                .clone()
+                .unwrap_or_else(|| config.base_url.clone()),
+            sender,
+            provider
+                .api_key
+                .clone()
+                .unwrap_or_else(|| config.api_key.clone()),
+            config.timeout(),
+            retry_policy,
+            config.batch_size,
+        )),
+        EmailProvider::Smtp => {
+            let smtp = config
+                .smtp
+                .as_ref()
+                .context("The smtp provider requires an [email_client.smtp] section")?;
+            Arc::new(SmtpEmailClient::new(
+                smtp,
+                sender,
+                config.timeout(),
+                retry_policy,
+            )?)
+        }
+        EmailProvider::Outbox => {
+            Arc::new(OutboxEmailSender::new(config.outbox_directory()?, sender))
+        }
+    };
+    Ok(email_sender)
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+
+    use crate::{
+        configuration::{get_configuration, EmailClientSettings, EmailProvider, FallbackSettings},
+        domain::SubscriberEmail,
+        email_client::{build_email_sender, EmailMessage},
+    };
+
+    fn failing_over_to(primary: &MockServer, fallback: &MockServer) -> EmailClientSettings {
+        let mut config = get_configuration().unwrap().email_client;
+        config.provider = EmailProvider::ElasticEmail;
+        config.base_url = primary.uri();
+        config.retry.max_attempts = 3;
+        config.retry.base_delay_milliseconds = 1;
+        config.retry.max_delay_milliseconds = 1;
+        config.fallbacks = vec![FallbackSettings {
+            provider: EmailProvider::ElasticEmail,
+            base_url: Some(fallback.uri()),
+            api_key: None,
+        }];
+        config
+    }
+
+    async fn send(config: &EmailClientSettings) {
+        let email_sender = build_email_sender(config, config.sender().unwrap()).unwrap();
+        let _ = email_sender
+            .send_email(
+                SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+                &EmailMessage::new("Subject", "<p>Body</p>", "Body"),
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn only_the_last_provider_retries() {
+        let primary = MockServer::start().await;
+        let fallback = MockServer::start().await;
+        for mock_server in [&primary, &fallback] {
+            Mock::given(any())
+                .respond_with(ResponseTemplate::new(503))
+                .mount(mock_server)
+                .await;
+        }
+
+        send(&failing_over_to(&primary, &fallback)).await;
+
+        assert_eq!(primary.received_requests().await.unwrap().len(), 1);
+        assert_eq!(fallback.received_requests().await.unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,209 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    domain::SubscriberEmail,
+    email_client::{DeliveryReceipt, EmailMessage, EmailSender, SendEmailError, Sender},
+};
+
+// Creates the outbox directory in XDG_CACHE_HOME, next to the log files
+pub fn outbox_directory() -> Result<PathBuf> {
+    let xdg_dirs = xdg::BaseDirectories::with_prefix("zero2prod")?;
+    Ok(xdg_dirs.create_cache_directory("outbox")?)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxEmail {
+    pub id: Uuid,
+    pub sent_at: DateTime<Utc>,
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+    #[serde(default)]
+    pub reply_to: Option<String>,
+    #[serde(default)]
+    pub attachments: Vec<OutboxAttachment>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OutboxAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content_id: Option<String>,
+    /// Base64 encoded.
+    pub content: String,
+}
+
+/// Writes each email as a JSON file into a directory instead of sending it.
+pub struct OutboxEmailSender {
+    directory: PathBuf,
+    sender: Sender,
+}
+
+impl OutboxEmailSender {
+    pub fn new(directory: PathBuf, sender: impl Into<Sender>) -> Self {
+        Self {
+            directory,
+            sender: sender.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailSender for OutboxEmailSender {
+    async fn send_email(
+        &self,
+        recipient: SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<DeliveryReceipt, SendEmailError> {
+        let from = match &self.sender.name {
+            Some(name) => format!("{name} <{}>", self.sender.email.as_ref()),
+            None => self.sender.email.as_ref().to_string(),
+        };
+        let email = OutboxEmail {
+            id: Uuid::new_v4(),
+            sent_at: Utc::now(),
+            from,
+            to: recipient.as_ref().to_string(),
+            subject: message.subject.clone(),
+            html_content: message.html_content.clone(),
+            text_content: message.text_content.clone(),
+            reply_to: message
+                .reply_to
+                .as_ref()
+                .map(|reply_to| reply_to.as_ref().to_string()),
+            attachments: message
+                .attachments
+                .iter()
+                .map(|attachment| OutboxAttachment {
+                    filename: attachment.filename.clone(),
+                    content_type: attachment.content_type.clone(),
+                    content_id: attachment.content_id.clone(),
+                    content: base64::engine::general_purpose::STANDARD.encode(&attachment.content),
+                })
+                .collect(),
+            tags: message.tags.clone(),
+            headers: message.headers().to_vec(),
+        };
+        let path = self.directory.join(format!(
+            "{}__{}.json",
+            email.sent_at.format("%Y_%m_%d__%H_%M_%S"),
+            email.id
+        ));
+        let contents = serde_json::to_vec_pretty(&email).context("Failed to serialize email")?;
+        tokio::fs::write(&path, contents)
+            .await
+            .with_context(|| format!("Failed to write email to {}", path.display()))?;
+        tracing::info!("Wrote email to {} into {}", email.to, path.display());
+        Ok(DeliveryReceipt {
+            message_id: Some(email.id.to_string()),
+            transaction_id: None,
+        })
+    }
+}
+
+/// Reads the emails in an outbox directory, newest first. Files that aren't emails are skipped.
+pub async fn read_outbox(directory: &Path) -> Result<Vec<OutboxEmail>> {
+    let mut entries = tokio::fs::read_dir(directory)
+        .await
+        .with_context(|| format!("Failed to read outbox {}", directory.display()))?;
+    let mut emails = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.path().extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let contents = tokio::fs::read(entry.path()).await?;
+        match serde_json::from_slice::<OutboxEmail>(&contents) {
+            Ok(email) => emails.push(email),
+            Err(e) => tracing::warn!("Skipping {}: {e}", entry.path().display()),
+        }
+    }
+    emails.sort_by_key(|email| std::cmp::Reverse(email.sent_at));
+    Ok(emails)
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_ok;
+    use fake::{faker::internet::en::SafeEmail, Fake};
+
+    use crate::{
+        domain::SubscriberEmail,
+        email_client::{read_outbox, EmailMessage, EmailSender, OutboxEmailSender},
+    };
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    fn temporary_directory() -> std::path::PathBuf {
+        let directory = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[tokio::test]
+    async fn sent_emails_can_be_read_back_newest_first() {
+        let directory = temporary_directory();
+        let email_sender = OutboxEmailSender::new(directory.clone(), email());
+        let recipient = email();
+
+        assert_ok!(
+            email_sender
+                .send_email(
+                    SubscriberEmail::parse(recipient.as_ref().into()).unwrap(),
+                    &EmailMessage::new("first", "<p>first</p>", "first")
+                        .attachment("invoice.pdf", "application/pdf", b"%PDF".to_vec())
+                        .header("X-Campaign", "spring")
+                        .unwrap(),
+                )
+                .await
+        );
+        assert_ok!(
+            email_sender
+                .send_email(
+                    email(),
+                    &EmailMessage::new("second", "<p>second</p>", "second")
+                )
+                .await
+        );
+
+        let emails = read_outbox(&directory).await.unwrap();
+        assert_eq!(emails.len(), 2);
+        assert_eq!(emails[0].subject, "second");
+        assert_eq!(emails[1].subject, "first");
+        assert_eq!(emails[1].to, recipient.as_ref());
+        assert_eq!(emails[1].html_content, "<p>first</p>");
+        assert_eq!(emails[1].attachments[0].filename, "invoice.pdf");
+        assert_eq!(emails[1].attachments[0].content, "JVBERg==");
+        assert_eq!(
+            emails[1].headers,
+            vec![("X-Campaign".to_string(), "spring".to_string())]
+        );
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unrelated_files_are_skipped() {
+        let directory = temporary_directory();
+        std::fs::write(directory.join("notes.txt"), "not an email").unwrap();
+        std::fs::write(directory.join("broken.json"), "{").unwrap();
+
+        let emails = read_outbox(&directory).await.unwrap();
+
+        assert!(emails.is_empty());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+
+use crate::{
+    domain::SubscriberEmail,
+    email_client::{
+        DeliveryReceipt, EmailMessage, EmailSender, ProviderHealth, RecipientOutcome,
+        SendEmailError,
+    },
+};
+
+/// Sends through an email backend that can be replaced while the application runs,
+/// e.g. when the configuration is reloaded with a rotated API key. Sends already
+/// under way finish on the backend they started on.
+pub struct ReloadableEmailSender {
+    inner: ArcSwap<Arc<dyn EmailSender>>,
+}
+
+impl ReloadableEmailSender {
+    pub fn new(inner: Arc<dyn EmailSender>) -> Self {
+        Self {
+            inner: ArcSwap::from_pointee(inner),
+        }
+    }
+
+    pub fn replace(&self, inner: Arc<dyn EmailSender>) {
+        self.inner.store(Arc::new(inner));
+    }
+}
+
+#[async_trait]
+impl EmailSender for ReloadableEmailSender {
+    async fn send_email(
+        &self,
+        recipient: SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<DeliveryReceipt, SendEmailError> {
+        let inner = self.inner.load_full();
+        inner.send_email(recipient, message).await
+    }
+
+    async fn send_batch(
+        &self,
+        recipients: &[SubscriberEmail],
+        message: &EmailMessage,
+    ) -> Vec<RecipientOutcome> {
+        let inner = self.inner.load_full();
+        inner.send_batch(recipients, message).await
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        self.inner.load().health()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use claims::assert_ok;
+
+    use crate::{
+        domain::SubscriberEmail,
+        email_client::{EmailMessage, EmailSender, InMemoryEmailSender, ReloadableEmailSender},
+    };
+
+    #[tokio::test]
+    async fn emails_go_to_the_replacement_after_a_reload() {
+        let before = Arc::new(InMemoryEmailSender::new());
+        let after = Arc::new(InMemoryEmailSender::new());
+        let email_sender = ReloadableEmailSender::new(before.clone());
+        let recipient = SubscriberEmail::parse("ursula@example.com".into()).unwrap();
+        let message = EmailMessage::new("Spring issue", "<p>Hello</p>", "Hello");
+
+        assert_ok!(email_sender.send_email(recipient.clone(), &message).await);
+        email_sender.replace(after.clone());
+        assert_ok!(email_sender.send_email(recipient, &message).await);
+
+        assert_eq!(before.sent_emails().len(), 1);
+        assert_eq!(after.sent_emails().len(), 1);
+    }
+}
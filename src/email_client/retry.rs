@@ -0,0 +1,154 @@
+use std::{future::Future, time::Duration};
+
+use rand::Rng;
+
+use crate::email_client::SendEmailError;
+
+/// Retries transient send failures with exponential backoff and full jitter.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// A random delay between zero and `base_delay * 2^attempt`, capped at `max_delay`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        rand::thread_rng().gen_range(Duration::ZERO..=ceiling)
+    }
+
+    /// Runs `send` until it succeeds, fails permanently or runs out of attempts.
+    /// A `Retry-After` longer than `max_delay` is returned to the caller instead of waited on.
+    pub async fn run<T, F, Fut>(&self, mut send: F) -> Result<T, SendEmailError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, SendEmailError>>,
+    {
+        let mut attempt = 0;
+        loop {
+            let error = match send().await {
+                Ok(sent) => return Ok(sent),
+                Err(e @ SendEmailError::Transient { .. }) => e,
+                Err(e) => return Err(e),
+            };
+            attempt += 1;
+            if attempt >= self.max_attempts {
+                return Err(error);
+            }
+            let delay = match error.retry_after() {
+                Some(retry_after) if retry_after > self.max_delay => return Err(error),
+                Some(retry_after) => retry_after,
+                None => self.backoff(attempt - 1),
+            };
+            tracing::warn!(
+                "Attempt {attempt} of {} failed, retrying in {delay:?}: {error}",
+                self.max_attempts
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::Duration};
+
+    use claims::{assert_err, assert_ok};
+
+    use crate::email_client::{RetryPolicy, SendEmailError};
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+        }
+    }
+
+    fn transient(retry_after: Option<Duration>) -> SendEmailError {
+        SendEmailError::Transient {
+            status: Some(503),
+            body: String::new(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn backoff_never_exceeds_the_maximum_delay() {
+        let policy = policy();
+        for attempt in 0..40 {
+            assert!(policy.backoff(attempt) <= policy.max_delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_until_success() {
+        let attempts = Mutex::new(0);
+        let outcome = policy()
+            .run(|| async {
+                *attempts.lock().unwrap() += 1;
+                if *attempts.lock().unwrap() < 3 {
+                    Err(transient(None))
+                } else {
+                    Ok(())
+                }
+            })
+            .await;
+
+        assert_ok!(outcome);
+        assert_eq!(*attempts.lock().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn transient_failures_give_up_after_the_maximum_attempts() {
+        let attempts = Mutex::new(0);
+        let outcome: Result<(), _> = policy()
+            .run(|| async {
+                *attempts.lock().unwrap() += 1;
+                Err(transient(None))
+            })
+            .await;
+
+        assert_err!(outcome);
+        assert_eq!(*attempts.lock().unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn permanent_failures_are_not_retried() {
+        let attempts = Mutex::new(0);
+        let outcome: Result<(), _> = policy()
+            .run(|| async {
+                *attempts.lock().unwrap() += 1;
+                Err(SendEmailError::Permanent {
+                    status: Some(400),
+                    body: "Bad request".into(),
+                })
+            })
+            .await;
+
+        assert!(matches!(outcome, Err(SendEmailError::Permanent { .. })));
+        assert_eq!(*attempts.lock().unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_after_beyond_the_maximum_delay_is_left_to_the_caller() {
+        let attempts = Mutex::new(0);
+        let outcome: Result<(), _> = policy()
+            .run(|| async {
+                *attempts.lock().unwrap() += 1;
+                Err(transient(Some(Duration::from_secs(60))))
+            })
+            .await;
+
+        assert_eq!(
+            outcome.unwrap_err().retry_after(),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(*attempts.lock().unwrap(), 1);
+    }
+}
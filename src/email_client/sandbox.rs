@@ -0,0 +1,272 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::{
+    domain::SubscriberEmail,
+    email_client::{
+        DeliveryReceipt, EmailMessage, EmailSender, ProviderHealth, RecipientOutcome,
+        SendEmailError,
+    },
+};
+
+/// Header carrying the recipient a redirected email was meant for.
+pub const ORIGINAL_RECIPIENT_HEADER: &str = "X-Sandbox-Original-Recipient";
+
+#[derive(Debug, Clone)]
+pub enum SandboxMode {
+    /// Every email goes to this address instead of its recipient.
+    Redirect(SubscriberEmail),
+    /// Only recipients at these domains get their email, the rest is dropped.
+    Allowlist(Vec<String>),
+    /// Nothing is sent, emails are only logged.
+    Record,
+}
+
+/// Keeps email from reaching real people, for staging environments and load tests.
+/// Emails that aren't sent as addressed are logged inside the request's span, so the
+/// log line carries its request id, and count as sent for the caller.
+pub struct SandboxEmailSender {
+    inner: Arc<dyn EmailSender>,
+    mode: SandboxMode,
+}
+
+impl SandboxEmailSender {
+    pub fn new(inner: Arc<dyn EmailSender>, mode: SandboxMode) -> Self {
+        let mode = match mode {
+            SandboxMode::Allowlist(domains) => SandboxMode::Allowlist(
+                domains
+                    .into_iter()
+                    .map(|domain| domain.to_lowercase())
+                    .collect(),
+            ),
+            mode => mode,
+        };
+        Self { inner, mode }
+    }
+
+    fn is_allowed(&self, recipient: &SubscriberEmail) -> bool {
+        match &self.mode {
+            SandboxMode::Redirect(_) | SandboxMode::Record => false,
+            SandboxMode::Allowlist(domains) => recipient
+                .as_ref()
+                .rsplit_once('@')
+                .map(|(_, domain)| domains.contains(&domain.to_lowercase()))
+                .unwrap_or(false),
+        }
+    }
+
+    fn hold_back(&self, recipient: &SubscriberEmail, message: &EmailMessage) -> DeliveryReceipt {
+        tracing::info!(
+            recipient = recipient.as_ref(),
+            subject = %message.subject,
+            "Sandbox: not sending email"
+        );
+        DeliveryReceipt::default()
+    }
+}
+
+#[async_trait]
+impl EmailSender for SandboxEmailSender {
+    async fn send_email(
+        &self,
+        recipient: SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<DeliveryReceipt, SendEmailError> {
+        match &self.mode {
+            SandboxMode::Redirect(redirect_to) => {
+                tracing::info!(
+                    recipient = recipient.as_ref(),
+                    redirect_to = redirect_to.as_ref(),
+                    subject = %message.subject,
+                    "Sandbox: redirecting email"
+                );
+                let message = message
+                    .clone()
+                    .header(ORIGINAL_RECIPIENT_HEADER, recipient.as_ref())?;
+                self.inner.send_email(redirect_to.clone(), &message).await
+            }
+            _ if self.is_allowed(&recipient) => self.inner.send_email(recipient, message).await,
+            _ => Ok(self.hold_back(&recipient, message)),
+        }
+    }
+
+    async fn send_batch(
+        &self,
+        recipients: &[SubscriberEmail],
+        message: &EmailMessage,
+    ) -> Vec<RecipientOutcome> {
+        if let SandboxMode::Redirect(_) = self.mode {
+            // Each redirected email names its own original recipient
+            let mut outcomes = Vec::with_capacity(recipients.len());
+            for recipient in recipients {
+                outcomes.push(RecipientOutcome {
+                    recipient: recipient.clone(),
+                    outcome: self.send_email(recipient.clone(), message).await,
+                });
+            }
+            return outcomes;
+        }
+        let allowed: Vec<SubscriberEmail> = recipients
+            .iter()
+            .filter(|recipient| self.is_allowed(recipient))
+            .cloned()
+            .collect();
+        let mut sent = self.inner.send_batch(&allowed, message).await.into_iter();
+        recipients
+            .iter()
+            .map(|recipient| {
+                if self.is_allowed(recipient) {
+                    sent.next()
+                        .unwrap_or_else(|| RecipientOutcome::missing(recipient))
+                } else {
+                    RecipientOutcome {
+                        recipient: recipient.clone(),
+                        outcome: Ok(self.hold_back(recipient, message)),
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        self.inner.health()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use claims::assert_ok;
+
+    use crate::{
+        domain::SubscriberEmail,
+        email_client::{
+            sandbox::ORIGINAL_RECIPIENT_HEADER, DeliveryReceipt, EmailMessage, EmailSender,
+            InMemoryEmailSender, RecipientOutcome, SandboxEmailSender, SandboxMode, SendEmailError,
+        },
+    };
+
+    fn email(address: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(address.into()).unwrap()
+    }
+
+    fn message() -> EmailMessage {
+        EmailMessage::new("Spring issue", "<p>Hello</p>", "Hello")
+    }
+
+    fn sandbox(mode: SandboxMode) -> (Arc<InMemoryEmailSender>, SandboxEmailSender) {
+        let inner = Arc::new(InMemoryEmailSender::new());
+        (inner.clone(), SandboxEmailSender::new(inner, mode))
+    }
+
+    #[tokio::test]
+    async fn redirect_mode_sends_everything_to_the_catch_all_address() {
+        let (inner, email_sender) = sandbox(SandboxMode::Redirect(email("sink@staging.test")));
+
+        assert_ok!(
+            email_sender
+                .send_email(email("ursula@example.com"), &message())
+                .await
+        );
+
+        let sent = inner.sent_emails();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].recipient, "sink@staging.test");
+        assert_eq!(
+            sent[0].message.headers(),
+            vec![(
+                ORIGINAL_RECIPIENT_HEADER.to_string(),
+                "ursula@example.com".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn allowlist_mode_only_delivers_to_allowed_domains() {
+        let (inner, email_sender) = sandbox(SandboxMode::Allowlist(vec!["Staging.test".into()]));
+
+        let outcomes = email_sender
+            .send_batch(
+                &[
+                    email("ursula@example.com"),
+                    email("qa@staging.test"),
+                    email("le_guin@example.com"),
+                ],
+                &message(),
+            )
+            .await;
+
+        assert!(outcomes.iter().all(|outcome| outcome.outcome.is_ok()));
+        let recipients: Vec<&str> = outcomes.iter().map(|o| o.recipient.as_ref()).collect();
+        assert_eq!(
+            recipients,
+            vec![
+                "ursula@example.com",
+                "qa@staging.test",
+                "le_guin@example.com"
+            ]
+        );
+        let sent = inner.sent_emails();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].recipient, "qa@staging.test");
+    }
+
+    #[tokio::test]
+    async fn record_mode_sends_nothing() {
+        let (inner, email_sender) = sandbox(SandboxMode::Record);
+
+        assert_ok!(
+            email_sender
+                .send_email(email("ursula@example.com"), &message())
+                .await
+        );
+        let outcomes = email_sender
+            .send_batch(&[email("le_guin@example.com")], &message())
+            .await;
+
+        assert!(outcomes[0].outcome.is_ok());
+        assert!(inner.sent_emails().is_empty());
+    }
+
+    /// Drops every outcome of a batch.
+    struct LosingEmailSender;
+
+    #[async_trait]
+    impl EmailSender for LosingEmailSender {
+        async fn send_email(
+            &self,
+            _recipient: SubscriberEmail,
+            _message: &EmailMessage,
+        ) -> Result<DeliveryReceipt, SendEmailError> {
+            Ok(DeliveryReceipt::default())
+        }
+
+        async fn send_batch(
+            &self,
+            _recipients: &[SubscriberEmail],
+            _message: &EmailMessage,
+        ) -> Vec<RecipientOutcome> {
+            Vec::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn recipients_missing_from_the_inner_batch_fail_instead_of_panicking() {
+        let email_sender = SandboxEmailSender::new(
+            Arc::new(LosingEmailSender),
+            SandboxMode::Allowlist(vec!["staging.test".into()]),
+        );
+
+        let outcomes = email_sender
+            .send_batch(&[email("qa@staging.test")], &message())
+            .await;
+
+        assert!(matches!(
+            outcomes[0].outcome,
+            Err(SendEmailError::Unexpected(_))
+        ));
+    }
+}
@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    email_client::DeliveryReceipt,
+    suppressions::{suppress, SuppressionReason},
+};
+
+/// What a provider reported about an email it sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailEventKind {
+    Delivered,
+    /// The recipient's server turned the email away for now, e.g. a full mailbox or greylisting.
+    SoftBounce,
+    /// The address doesn't exist or will never accept our email.
+    HardBounce,
+    /// The recipient marked the email as spam.
+    Complaint,
+}
+
+impl EmailEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailEventKind::Delivered => "delivered",
+            EmailEventKind::SoftBounce => "soft_bounce",
+            EmailEventKind::HardBounce => "hard_bounce",
+            EmailEventKind::Complaint => "complaint",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmailEvent {
+    pub kind: EmailEventKind,
+    pub recipient: String,
+    pub message_id: Option<String>,
+    pub transaction_id: Option<String>,
+    /// The provider's reason, e.g. its bounce category.
+    pub detail: Option<String>,
+}
+
+/// Providers can't sign their notifications, so they authenticate with the token in
+/// the notification URL they were given. Compared in constant time so the token
+/// can't be guessed a byte at a time.
+pub fn verify_token(token: &Secret<String>, expected: &Secret<String>) -> bool {
+    let token = token.expose_secret().as_bytes();
+    let expected = expected.expose_secret().as_bytes();
+    !expected.is_empty() && bool::from(token.ct_eq(expected))
+}
+
+/// Elastic Email's notification, sent as a form.
+#[derive(Deserialize)]
+struct ElasticEmailNotification {
+    to: String,
+    status: String,
+    category: Option<String>,
+    transaction: Option<String>,
+    messageid: Option<String>,
+}
+
+/// Parses an Elastic Email notification. Statuses we don't act on, like opens and clicks,
+/// give `None`.
+pub fn parse_elastic_email_notification(body: &[u8]) -> Result<Option<EmailEvent>> {
+    let notification: ElasticEmailNotification =
+        serde_urlencoded::from_bytes(body).context("Failed to parse Elastic Email notification")?;
+    let category = notification.category.filter(|c| !c.is_empty());
+    let kind = match notification.status.to_lowercase().as_str() {
+        "sent" => EmailEventKind::Delivered,
+        "abusereport" => EmailEventKind::Complaint,
+        "error" | "bounced" => match category.as_deref().map(str::to_lowercase).as_deref() {
+            Some("nomailbox" | "accountproblem" | "dnsproblem" | "blacklisted" | "notallowed") => {
+                EmailEventKind::HardBounce
+            }
+            Some("abusereport") => EmailEventKind::Complaint,
+            // Cancelled by us or failed on Elastic's side, the recipient is not to blame
+            Some("manualcancel" | "notdeliveredcancelled" | "codeerror") => return Ok(None),
+            _ => EmailEventKind::SoftBounce,
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(EmailEvent {
+        kind,
+        recipient: notification.to,
+        message_id: notification.messageid.filter(|id| !id.is_empty()),
+        transaction_id: notification.transaction.filter(|id| !id.is_empty()),
+        detail: category,
+    }))
+}
+
+/// Remembers an accepted email, so that provider events can be traced back to it.
+#[instrument(name = "Record email delivery", skip(connection, receipt))]
+pub async fn record_delivery(
+    connection: &PgPool,
+    recipient: &str,
+    receipt: &DeliveryReceipt,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO email_deliveries (id, recipient, message_id, transaction_id, sent_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        Uuid::new_v4(),
+        recipient,
+        receipt.message_id,
+        receipt.transaction_id,
+        Utc::now(),
+    )
+    .execute(connection)
+    .await
+    .context("Failed to record email delivery")?;
+    Ok(())
+}
+
+/// Records the event against its delivery and updates the subscriber it concerns:
+/// hard bounces and complaints suppress the address right away, soft bounces only
+/// after `soft_bounce_threshold` of them in a row. Returns false for an event seen before.
+#[instrument(name = "Record email event", skip(connection, payload))]
+pub async fn record_event(
+    connection: &PgPool,
+    provider: &str,
+    event: &EmailEvent,
+    payload: &[u8],
+    soft_bounce_threshold: i32,
+) -> Result<bool> {
+    let mut transaction = connection
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+    let delivery_id = sqlx::query!(
+        r#"
+        SELECT id FROM email_deliveries
+        WHERE message_id = $1 OR (transaction_id = $2 AND lower(recipient) = lower($3))
+        ORDER BY sent_at DESC
+        LIMIT 1
+        "#,
+        event.message_id,
+        event.transaction_id,
+        event.recipient,
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to look up email delivery")?
+    .map(|row| row.id);
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO email_events
+            (id, delivery_id, provider, kind, recipient, detail, payload_hash, received_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (provider, payload_hash) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        delivery_id,
+        provider,
+        event.kind.as_str(),
+        event.recipient,
+        event.detail,
+        hex::encode(Sha256::digest(payload)),
+        Utc::now(),
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to record email event")?
+    .rows_affected()
+        == 1;
+    if !inserted {
+        return Ok(false);
+    }
+
+    match event.kind {
+        EmailEventKind::Delivered => sqlx::query!(
+            r#"UPDATE subscriptions SET soft_bounces = 0 WHERE lower(email) = lower($1)"#,
+            event.recipient,
+        ),
+        EmailEventKind::SoftBounce => sqlx::query!(
+            r#"
+            UPDATE subscriptions
+            SET soft_bounces = soft_bounces + 1,
+                status = CASE
+                    WHEN status = 'confirmed' AND soft_bounces + 1 >= $2 THEN 'bounced'
+                    ELSE status
+                END
+            WHERE lower(email) = lower($1)
+            "#,
+            event.recipient,
+            soft_bounce_threshold,
+        ),
+        EmailEventKind::HardBounce => sqlx::query!(
+            r#"
+            UPDATE subscriptions SET status = 'bounced'
+            WHERE lower(email) = lower($1) AND status <> 'complained'
+            "#,
+            event.recipient,
+        ),
+        EmailEventKind::Complaint => sqlx::query!(
+            r#"UPDATE subscriptions SET status = 'complained' WHERE lower(email) = lower($1)"#,
+            event.recipient,
+        ),
+    }
+    .execute(&mut transaction)
+    .await
+    .context("Failed to update subscriber status")?;
+
+    let suppression = match event.kind {
+        EmailEventKind::Delivered => None,
+        EmailEventKind::SoftBounce => {
+            let soft_bounces = sqlx::query!(
+                r#"SELECT soft_bounces FROM subscriptions WHERE lower(email) = lower($1)"#,
+                event.recipient,
+            )
+            .fetch_optional(&mut transaction)
+            .await
+            .context("Failed to count soft bounces")?
+            .map(|row| row.soft_bounces);
+            (soft_bounces >= Some(soft_bounce_threshold)).then_some(SuppressionReason::SoftBounce)
+        }
+        EmailEventKind::HardBounce => Some(SuppressionReason::HardBounce),
+        EmailEventKind::Complaint => Some(SuppressionReason::Complaint),
+    };
+    if let Some(reason) = suppression {
+        suppress(
+            &mut transaction,
+            &event.recipient,
+            reason,
+            &format!("webhook:{provider}"),
+            event.detail.as_deref(),
+        )
+        .await?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_none, assert_ok_eq};
+    use secrecy::Secret;
+
+    use crate::email_events::{
+        parse_elastic_email_notification, verify_token, EmailEvent, EmailEventKind,
+    };
+
+    fn token(token: &str) -> Secret<String> {
+        Secret::new(token.into())
+    }
+
+    #[test]
+    fn the_configured_token_is_accepted() {
+        assert!(verify_token(
+            &token("webhook-token"),
+            &token("webhook-token")
+        ));
+    }
+
+    #[test]
+    fn other_tokens_are_rejected() {
+        assert!(!verify_token(
+            &token("other-token"),
+            &token("webhook-token")
+        ));
+        assert!(!verify_token(&token("webhook"), &token("webhook-token")));
+        assert!(!verify_token(&token(""), &token("webhook-token")));
+    }
+
+    #[test]
+    fn nothing_is_accepted_without_a_configured_token() {
+        assert!(!verify_token(&token(""), &token("")));
+    }
+
+    #[test]
+    fn elastic_email_bounce_categories_are_classified() {
+        let parse = |category: &str| {
+            parse_elastic_email_notification(
+                format!("to=ursula%40example.com&status=Error&category={category}").as_bytes(),
+            )
+            .unwrap()
+            .map(|event| event.kind)
+        };
+
+        assert_eq!(parse("NoMailbox"), Some(EmailEventKind::HardBounce));
+        assert_eq!(parse("DNSProblem"), Some(EmailEventKind::HardBounce));
+        assert_eq!(parse("GreyListed"), Some(EmailEventKind::SoftBounce));
+        assert_eq!(parse("Timeout"), Some(EmailEventKind::SoftBounce));
+        assert_eq!(parse("ManualCancel"), None);
+    }
+
+    #[test]
+    fn elastic_email_notifications_are_parsed() {
+        let event = parse_elastic_email_notification(
+            b"to=ursula%40example.com&status=AbuseReport&transaction=t-1&messageid=m-1&category=",
+        );
+        assert_ok_eq!(
+            event,
+            Some(EmailEvent {
+                kind: EmailEventKind::Complaint,
+                recipient: "ursula@example.com".into(),
+                message_id: Some("m-1".into()),
+                transaction_id: Some("t-1".into()),
+                detail: None,
+            })
+        );
+    }
+
+    #[test]
+    fn opens_and_clicks_are_ignored() {
+        let event = parse_elastic_email_notification(b"to=ursula%40example.com&status=Opened");
+        assert_none!(event.unwrap());
+    }
+}
@@ -1,6 +1,17 @@
+pub mod authentication;
+pub mod client_ip;
 pub mod configuration;
 pub mod domain;
 pub mod email_client;
+pub mod email_events;
+pub mod html;
+pub mod mailsink;
+pub mod reload;
 pub mod routes;
+pub mod shutdown;
 pub mod startup;
+pub mod subscribers;
+pub mod suppressions;
 pub mod telemetry;
+pub mod tls;
+pub mod user_tokens;
@@ -0,0 +1,531 @@
+//! A stand-in for Elastic Email's `/email/send` API, for local development and tests.
+//! It validates requests the way the real API does, keeps what it receives in memory
+//! and shows it over a JSON API and a simple HTML page.
+
+use std::{
+    collections::HashMap,
+    net::TcpListener,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::Result;
+use axum::{
+    body::Body,
+    extract::{FromRequest, Multipart, Query, State},
+    http::{header::CONTENT_TYPE, Request, StatusCode},
+    response::Html,
+    routing::{get, post},
+    Form, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{domain::SubscriberEmail, html::escape_html};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedEmail {
+    pub message_id: String,
+    pub transaction_id: String,
+    pub received_at: DateTime<Utc>,
+    pub from: String,
+    pub from_name: Option<String>,
+    pub to: String,
+    pub reply_to: Option<String>,
+    pub subject: String,
+    pub body_html: Option<String>,
+    pub body_text: Option<String>,
+    pub channel: Option<String>,
+    pub is_transactional: bool,
+    pub headers: Vec<(String, String)>,
+    pub attachments: Vec<ReceivedAttachment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub size: usize,
+}
+
+/// The emails received so far, oldest first.
+#[derive(Clone, Default)]
+pub struct MailSink {
+    emails: Arc<Mutex<Vec<ReceivedEmail>>>,
+}
+
+impl MailSink {
+    pub fn emails(&self) -> Vec<ReceivedEmail> {
+        self.emails.lock().expect("Mail sink lock poisoned").clone()
+    }
+    fn store(&self, emails: Vec<ReceivedEmail>) {
+        self.emails
+            .lock()
+            .expect("Mail sink lock poisoned")
+            .extend(emails);
+    }
+    fn clear(&self) {
+        self.emails.lock().expect("Mail sink lock poisoned").clear();
+    }
+}
+
+type Server =
+    axum::Server<hyper::server::conn::AddrIncoming, axum::routing::IntoMakeService<Router>>;
+
+pub fn run(listener: TcpListener, sink: MailSink) -> Result<Server> {
+    let app = Router::new()
+        .route("/", get(inbox))
+        .route("/email/send", post(send_email))
+        .route("/api/emails", get(list_emails).delete(delete_emails))
+        .with_state(sink);
+    tracing::info!("Mail sink listening on {}", listener.local_addr()?);
+    Ok(axum::Server::from_tcp(listener)?.serve(app.into_make_service()))
+}
+
+/// Fields from either a urlencoded or a multipart form. Names are matched
+/// case-insensitively, like Elastic Email does.
+struct SendEmailFields {
+    fields: HashMap<String, String>,
+    headers: Vec<(String, String)>,
+    attachments: Vec<ReceivedAttachment>,
+}
+
+impl SendEmailFields {
+    fn new(fields: Vec<(String, String)>, attachments: Vec<ReceivedAttachment>) -> Self {
+        let mut headers = Vec::new();
+        let mut named = HashMap::new();
+        for (name, value) in fields {
+            if name.to_lowercase().starts_with("headers_") {
+                // The value is the whole header line
+                if let Some((name, value)) = value.split_once(':') {
+                    headers.push((name.trim().to_string(), value.trim().to_string()));
+                }
+            } else {
+                named.insert(name.to_lowercase(), value);
+            }
+        }
+        Self {
+            fields: named,
+            headers,
+            attachments,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.get(name)
+            .ok_or_else(|| format!("Missing required field {name}"))
+    }
+}
+
+async fn read_fields(request: Request<Body>) -> Result<SendEmailFields, String> {
+    let is_multipart = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("multipart/form-data"));
+    if !is_multipart {
+        let Form(fields) = Form::<Vec<(String, String)>>::from_request(request, &())
+            .await
+            .map_err(|e| e.to_string())?;
+        return Ok(SendEmailFields::new(fields, Vec::new()));
+    }
+    let mut multipart = Multipart::from_request(request, &())
+        .await
+        .map_err(|e| e.to_string())?;
+    let mut fields = Vec::new();
+    let mut attachments = Vec::new();
+    while let Some(field) = multipart.next_field().await.map_err(|e| e.to_string())? {
+        let name = field.name().unwrap_or_default().to_string();
+        match field.file_name().map(str::to_string) {
+            Some(filename) => {
+                let content_type = field
+                    .content_type()
+                    .unwrap_or("application/octet-stream")
+                    .to_string();
+                let content = field.bytes().await.map_err(|e| e.to_string())?;
+                attachments.push(ReceivedAttachment {
+                    filename,
+                    content_type,
+                    size: content.len(),
+                });
+            }
+            None => fields.push((name, field.text().await.map_err(|e| e.to_string())?)),
+        }
+    }
+    Ok(SendEmailFields::new(fields, attachments))
+}
+
+fn parse_email(field: &str, address: &str) -> Result<String, String> {
+    SubscriberEmail::parse(address.trim().to_string())
+        .map(|email| email.as_ref().to_string())
+        .map_err(|_| format!("Invalid email address {address} in {field}"))
+}
+
+/// One email per recipient, each with its own message id and sharing the transaction id.
+fn validate(fields: &SendEmailFields) -> Result<Vec<ReceivedEmail>, String> {
+    fields.required("apikey")?;
+    let from = parse_email("From", fields.required("from")?)?;
+    let subject = fields.required("subject")?.to_string();
+    let recipients = match (fields.get("to"), fields.get("msgto")) {
+        (Some(to), None) => vec![parse_email("To", to)?],
+        (None, Some(msg_to)) => msg_to
+            .split(',')
+            .map(|recipient| parse_email("MsgTo", recipient))
+            .collect::<Result<_, _>>()?,
+        (Some(_), Some(_)) => return Err("Only one of To and MsgTo can be set".into()),
+        (None, None) => return Err("Missing required field To or MsgTo".into()),
+    };
+    let body_html = fields.get("bodyhtml").map(str::to_string);
+    let body_text = fields.get("bodytext").map(str::to_string);
+    if body_html.is_none() && body_text.is_none() {
+        return Err("Missing required field BodyHtml or BodyText".into());
+    }
+    let reply_to = fields
+        .get("replyto")
+        .map(|reply_to| parse_email("ReplyTo", reply_to))
+        .transpose()?;
+    let is_transactional = match fields.get("istransactional") {
+        None => false,
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("Invalid boolean {value} in IsTransactional"))?,
+    };
+
+    let transaction_id = Uuid::new_v4().to_string();
+    let received_at = Utc::now();
+    Ok(recipients
+        .into_iter()
+        .map(|to| ReceivedEmail {
+            message_id: Uuid::new_v4().to_string(),
+            transaction_id: transaction_id.clone(),
+            received_at,
+            from: from.clone(),
+            from_name: fields.get("fromname").map(str::to_string),
+            to,
+            reply_to: reply_to.clone(),
+            subject: subject.clone(),
+            body_html: body_html.clone(),
+            body_text: body_text.clone(),
+            channel: fields.get("channel").map(str::to_string),
+            is_transactional,
+            headers: fields.headers.clone(),
+            attachments: fields.attachments.clone(),
+        })
+        .collect())
+}
+
+/// Answers like Elastic Email, with a 200 and `success: false` for invalid requests.
+/// The message id is only returned for a single `To` recipient.
+async fn send_email(
+    State(sink): State<MailSink>,
+    request: Request<Body>,
+) -> Json<serde_json::Value> {
+    let emails = match read_fields(request).await.and_then(|f| validate(&f)) {
+        Ok(emails) => emails,
+        Err(error) => {
+            tracing::warn!("Rejecting email: {error}");
+            return Json(json!({ "success": false, "error": error }));
+        }
+    };
+    let transaction_id = emails[0].transaction_id.clone();
+    let message_id = (emails.len() == 1).then(|| emails[0].message_id.clone());
+    for email in &emails {
+        tracing::info!("Received email for {}: {}", email.to, email.subject);
+    }
+    sink.store(emails);
+    Json(json!({
+        "success": true,
+        "data": { "transactionid": transaction_id, "messageid": message_id },
+    }))
+}
+
+#[derive(Deserialize)]
+struct EmailFilter {
+    to: Option<String>,
+}
+
+/// The received emails, newest first, optionally only those sent to `to`.
+async fn list_emails(
+    State(sink): State<MailSink>,
+    Query(filter): Query<EmailFilter>,
+) -> Json<Vec<ReceivedEmail>> {
+    let mut emails = sink.emails();
+    emails.reverse();
+    if let Some(to) = filter.to {
+        emails.retain(|email| email.to.eq_ignore_ascii_case(&to));
+    }
+    Json(emails)
+}
+
+async fn delete_emails(State(sink): State<MailSink>) -> StatusCode {
+    sink.clear();
+    StatusCode::NO_CONTENT
+}
+
+async fn inbox(State(sink): State<MailSink>) -> Html<String> {
+    let emails = sink.emails();
+    let mut page = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="utf-8"><title>Mail sink</title></head>
+<body>
+<h1>Mail sink</h1>
+<p>{} emails received</p>
+"#,
+        emails.len()
+    );
+    for email in emails.iter().rev() {
+        page.push_str(&format!(
+            r#"<article>
+<h2>{}</h2>
+<p>From {} to {} at {}</p>
+<pre>{}</pre>
+</article>
+"#,
+            escape_html(&email.subject),
+            escape_html(&email.from),
+            escape_html(&email.to),
+            email.received_at.to_rfc3339(),
+            escape_html(email.body_text.as_deref().unwrap_or_default()),
+        ));
+    }
+    page.push_str("</body>\n</html>\n");
+    Html(page)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use claims::assert_ok;
+    use secrecy::Secret;
+
+    use crate::{
+        domain::SubscriberEmail,
+        email_client::{ElasticEmailClient, EmailMessage, EmailSender, RetryPolicy},
+        mailsink::{run, MailSink},
+    };
+
+    fn spawn_sink() -> (String, MailSink) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let sink = MailSink::default();
+        tokio::spawn(run(listener, sink.clone()).unwrap());
+        (address, sink)
+    }
+
+    fn email_client(base_url: String) -> ElasticEmailClient {
+        ElasticEmailClient::new(
+            base_url,
+            SubscriberEmail::parse("news@example.com".into()).unwrap(),
+            Secret::new("api-key".into()),
+            std::time::Duration::from_secs(2),
+            RetryPolicy {
+                max_attempts: 1,
+                base_delay: std::time::Duration::ZERO,
+                max_delay: std::time::Duration::ZERO,
+            },
+            2,
+        )
+    }
+
+    fn email(address: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(address.into()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn emails_sent_by_the_elastic_email_client_are_received() {
+        let (address, sink) = spawn_sink();
+        let message = EmailMessage::new("Spring issue", "<p>Hello</p>", "Hello")
+            .reply_to(email("help@example.com"))
+            .tag("spring")
+            .header("X-Campaign", "spring")
+            .unwrap()
+            .attachment("invoice.pdf", "application/pdf", b"%PDF".to_vec());
+
+        let receipt = email_client(address)
+            .send_email(email("ursula@example.com"), &message)
+            .await
+            .unwrap();
+
+        let emails = sink.emails();
+        assert_eq!(emails.len(), 1);
+        let received = &emails[0];
+        assert_eq!(receipt.message_id.as_ref(), Some(&received.message_id));
+        assert_eq!(
+            receipt.transaction_id.as_ref(),
+            Some(&received.transaction_id)
+        );
+        assert_eq!(received.from, "news@example.com");
+        assert_eq!(received.to, "ursula@example.com");
+        assert_eq!(received.reply_to.as_deref(), Some("help@example.com"));
+        assert_eq!(received.subject, "Spring issue");
+        assert_eq!(received.body_html.as_deref(), Some("<p>Hello</p>"));
+        assert_eq!(received.body_text.as_deref(), Some("Hello"));
+        assert_eq!(received.channel.as_deref(), Some("spring"));
+        assert!(received.is_transactional);
+        assert_eq!(
+            received.headers,
+            vec![("X-Campaign".to_string(), "spring".to_string())]
+        );
+        assert_eq!(received.attachments[0].filename, "invoice.pdf");
+        assert_eq!(received.attachments[0].size, 4);
+    }
+
+    #[tokio::test]
+    async fn batches_are_received_as_one_email_per_recipient() {
+        let (address, sink) = spawn_sink();
+        let recipients = [
+            email("ursula@example.com"),
+            email("le_guin@example.com"),
+            email("earthsea@example.com"),
+        ];
+
+        let outcomes = email_client(address)
+            .send_batch(&recipients, &EmailMessage::new("Spring", "html", "text"))
+            .await;
+
+        assert!(outcomes.iter().all(|outcome| outcome.outcome.is_ok()));
+        let received: Vec<String> = sink.emails().into_iter().map(|email| email.to).collect();
+        assert_eq!(
+            received,
+            vec![
+                "ursula@example.com",
+                "le_guin@example.com",
+                "earthsea@example.com"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_missing_required_fields_are_rejected() {
+        let (address, sink) = spawn_sink();
+        let cases = [
+            (
+                vec![
+                    ("From", "news@example.com"),
+                    ("To", "u@example.com"),
+                    ("Subject", "s"),
+                    ("BodyText", "t"),
+                ],
+                "apikey",
+            ),
+            (
+                vec![
+                    ("Apikey", "key"),
+                    ("To", "u@example.com"),
+                    ("Subject", "s"),
+                    ("BodyText", "t"),
+                ],
+                "from",
+            ),
+            (
+                vec![
+                    ("Apikey", "key"),
+                    ("From", "news@example.com"),
+                    ("Subject", "s"),
+                    ("BodyText", "t"),
+                ],
+                "To or MsgTo",
+            ),
+            (
+                vec![
+                    ("Apikey", "key"),
+                    ("From", "news@example.com"),
+                    ("To", "u@example.com"),
+                    ("BodyText", "t"),
+                ],
+                "subject",
+            ),
+            (
+                vec![
+                    ("Apikey", "key"),
+                    ("From", "news@example.com"),
+                    ("To", "u@example.com"),
+                    ("Subject", "s"),
+                ],
+                "BodyHtml or BodyText",
+            ),
+            (
+                vec![
+                    ("Apikey", "key"),
+                    ("From", "news@example.com"),
+                    ("To", "not an email"),
+                    ("Subject", "s"),
+                    ("BodyText", "t"),
+                ],
+                "Invalid email address",
+            ),
+        ];
+
+        for (form, error) in cases {
+            let response: serde_json::Value = reqwest::Client::new()
+                .post(format!("{address}/email/send"))
+                .form(&form)
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            assert_eq!(response["success"], false, "{form:?}");
+            let message = response["error"].as_str().unwrap();
+            assert!(message.contains(error), "{message} for {form:?}");
+        }
+        assert!(sink.emails().is_empty());
+    }
+
+    #[tokio::test]
+    async fn received_emails_are_listed_newest_first_and_can_be_cleared() {
+        let (address, _) = spawn_sink();
+        let email_client = email_client(address.clone());
+        for recipient in ["ursula@example.com", "le_guin@example.com"] {
+            assert_ok!(
+                email_client
+                    .send_email(email(recipient), &EmailMessage::new("s", "h", "t"))
+                    .await
+            );
+        }
+
+        let listed: Vec<serde_json::Value> = reqwest::get(format!("{address}/api/emails"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(listed[0]["to"], "le_guin@example.com");
+        assert_eq!(listed[1]["to"], "ursula@example.com");
+        let filtered: Vec<serde_json::Value> =
+            reqwest::get(format!("{address}/api/emails?to=Ursula@example.com"))
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+        assert_eq!(filtered.len(), 1);
+
+        let page = reqwest::get(&address).await.unwrap().text().await.unwrap();
+        assert!(page.contains("2 emails received"));
+
+        reqwest::Client::new()
+            .delete(format!("{address}/api/emails"))
+            .send()
+            .await
+            .unwrap();
+        let listed: Vec<serde_json::Value> = reqwest::get(format!("{address}/api/emails"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert!(listed.is_empty());
+    }
+}
@@ -1,24 +1,258 @@
+use std::{
+    fs::File,
+    io::{BufRead, Read, Write},
+    path::PathBuf,
+    process::ExitCode,
+    sync::Arc,
+};
+
+use anyhow::Context;
+use clap::{Parser, Subcommand, ValueEnum};
+use secrecy::Secret;
 use zero2prod::{
-    configuration::get_configuration,
+    authentication::{create_user, UserRole},
+    configuration::{get_configuration_with, get_effective_configuration, ConfigArgs},
+    domain::{NewPassword, SubscriberEmail, Username},
+    email_client::{build_email_sender, EmailMessage, EmailSender},
+    reload::reload_on_sighup,
+    startup::{get_connection_pool, run_migrations},
+    subscribers::{export_subscribers, import_subscribers},
+    suppressions::SuppressingEmailSender,
     telemetry::{get_log_file, get_subscriber, init_subscriber},
 };
 
-#[tokio::main]
-async fn main() -> std::io::Result<()> {
+/// The newsletter API server.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    #[command(flatten)]
+    config: ConfigArgs,
+    /// Serves the API when left out.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the API, the default.
+    Serve,
+    /// Apply the database migrations built into this binary.
+    Migrate,
+    /// Create an account that can log in right away, reading its password from standard input.
+    CreateAdmin {
+        #[arg(long)]
+        username: String,
+        #[arg(long)]
+        email: String,
+        /// Make it an owner, who can invite other admins.
+        #[arg(long)]
+        owner: bool,
+    },
+    /// Send an email through the configured providers to check they work.
+    SendTestEmail { address: String },
+    /// Import or export the subscriber list as CSV.
+    #[command(subcommand)]
+    Subscribers(SubscribersCommand),
+    /// Inspect the configuration.
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Subcommand)]
+enum SubscribersCommand {
+    /// Subscribe everyone in a CSV with email and name columns, skipping suppressed addresses.
+    Import {
+        /// Reads standard input when left out.
+        file: Option<PathBuf>,
+    },
+    /// Write every subscriber as CSV.
+    Export {
+        /// Writes to standard output when left out.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print the effective configuration with where each value came from, secrets redacted.
+    Show {
+        #[arg(long, value_enum, default_value_t = Format::Toml)]
+        format: Format,
+    },
+    /// Exit with an error if the configuration is invalid.
+    Check,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Toml,
+    Json,
+}
+
+fn config_command(command: ConfigCommand, args: &ConfigArgs) -> anyhow::Result<()> {
+    match command {
+        ConfigCommand::Show { format } => {
+            let config = get_effective_configuration(args)?;
+            match format {
+                Format::Toml => print!("{}", config.to_toml()?),
+                Format::Json => println!("{}", config.to_json()?),
+            }
+        }
+        ConfigCommand::Check => {
+            let config = get_configuration_with(args)?;
+            build_email_sender(&config.email_client, config.email_client.sender()?)?;
+            println!("The configuration is valid");
+        }
+    }
+    Ok(())
+}
+
+async fn migrate(args: &ConfigArgs) -> anyhow::Result<()> {
+    let config = get_configuration_with(args)?;
+    run_migrations(&get_connection_pool(&config.database)).await?;
+    println!("The database is up to date");
+    Ok(())
+}
+
+async fn create_admin(
+    args: &ConfigArgs,
+    username: String,
+    email: String,
+    owner: bool,
+) -> anyhow::Result<()> {
+    let config = get_configuration_with(args)?;
+    let username = Username::parse(username)?;
+    let email = SubscriberEmail::parse(email)?;
+    let mut password = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut password)
+        .context("Failed to read the password from standard input")?;
+    let password = NewPassword::parse(Secret::new(password.trim_end_matches(['\r', '\n']).into()))?;
+    let role = if owner {
+        UserRole::Owner
+    } else {
+        UserRole::Admin
+    };
+    let connection = get_connection_pool(&config.database);
+    let user_id = create_user(&connection, &username, &email, password, role).await?;
+    println!(
+        "Created {} {} ({user_id})",
+        role.as_str(),
+        username.as_ref()
+    );
+    Ok(())
+}
+
+async fn send_test_email(args: &ConfigArgs, address: String) -> anyhow::Result<()> {
+    let config = get_configuration_with(args)?;
+    let recipient = SubscriberEmail::parse(address)?;
+    let email_client = SuppressingEmailSender::new(
+        build_email_sender(&config.email_client, config.email_client.sender()?)?,
+        get_connection_pool(&config.database),
+    );
+    let receipt = email_client
+        .send_email(
+            recipient,
+            &EmailMessage::new(
+                "Test email from the newsletter",
+                "If you can read this, the newsletter can send emails.",
+                "If you can read this, the newsletter can send emails.",
+            ),
+        )
+        .await?;
+    println!(
+        "Sent, message id {}",
+        receipt.message_id.as_deref().unwrap_or("unknown")
+    );
+    Ok(())
+}
+
+async fn subscribers_command(command: SubscribersCommand, args: &ConfigArgs) -> anyhow::Result<()> {
+    let config = get_configuration_with(args)?;
+    let connection = get_connection_pool(&config.database);
+    match command {
+        SubscribersCommand::Import { file } => {
+            let input: Box<dyn Read> = match &file {
+                Some(file) => Box::new(
+                    File::open(file)
+                        .with_context(|| format!("Failed to open {}", file.display()))?,
+                ),
+                None => Box::new(std::io::stdin()),
+            };
+            let report = import_subscribers(&connection, input).await?;
+            for (line, problem) in &report.invalid {
+                eprintln!("Skipped line {line}: {problem}");
+            }
+            println!(
+                "Imported {}, {} already subscribed, {} suppressed, {} not confirmed, {} invalid",
+                report.imported,
+                report.already_subscribed,
+                report.suppressed,
+                report.not_confirmed,
+                report.invalid.len()
+            );
+        }
+        SubscribersCommand::Export { output } => {
+            let count = match &output {
+                Some(output) => {
+                    let file = File::create(output)
+                        .with_context(|| format!("Failed to create {}", output.display()))?;
+                    export_subscribers(&connection, file).await?
+                }
+                None => export_subscribers(&connection, std::io::stdout().lock()).await?,
+            };
+            std::io::stdout().flush()?;
+            eprintln!("Exported {count} subscribers");
+        }
+    }
+    Ok(())
+}
+
+async fn serve(args: ConfigArgs) -> anyhow::Result<()> {
+    let config = get_configuration_with(&args)?;
+
     let subscriber = get_subscriber(
         "zero2prod".into(),
-        "info".into(),
-        get_log_file("normal".into()).expect("Failed to get log file"),
+        config.application.log_level.clone(),
+        get_log_file("normal".into())?,
     );
     init_subscriber(subscriber);
 
-    let config = get_configuration().expect("Failed to read config");
+    let app =
+        zero2prod::startup::App::build(&config, Arc::new(move || get_configuration_with(&args)))
+            .await?;
+    let shutdown = app.shutdown();
+    let (reloader, stopping) = (app.reloader(), shutdown.stopping());
+    shutdown.spawn(async move {
+        if let Err(e) = reload_on_sighup(reloader, stopping).await {
+            tracing::error!("Stopped reloading the configuration on SIGHUP: {e:?}");
+        }
+    });
+    app.run_until_stopped().await
+}
 
-    let app = zero2prod::startup::App::build(&config)
-        .await
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-    app.run_until_stopped()
-        .await
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e.to_string()))?;
-    Ok(())
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Some(Command::Serve) | None => serve(cli.config).await,
+        Some(Command::Migrate) => migrate(&cli.config).await,
+        Some(Command::CreateAdmin {
+            username,
+            email,
+            owner,
+        }) => create_admin(&cli.config, username, email, owner).await,
+        Some(Command::SendTestEmail { address }) => send_test_email(&cli.config, address).await,
+        Some(Command::Subscribers(command)) => subscribers_command(command, &cli.config).await,
+        Some(Command::Config(command)) => config_command(command, &cli.config),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e:#}");
+            ExitCode::FAILURE
+        }
+    }
 }
@@ -0,0 +1,223 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio_util::sync::CancellationToken;
+use tracing::instrument;
+
+use crate::{
+    configuration::{AccountSettings, EmailWebhookSettings, LoginSettings, Settings},
+    email_client::{build_email_sender, ReloadableEmailSender},
+    telemetry::{set_log_filter, spawn_blocking_with_tracing},
+};
+
+/// Reads the configuration again, e.g. `get_configuration_with` and the command line's `ConfigArgs`.
+pub type LoadConfiguration = Arc<dyn Fn() -> Result<Settings> + Send + Sync>;
+
+/// The settings handlers read on every request. They are replaced as a whole on
+/// reload, so a request never sees half of the old and half of the new ones.
+pub struct LiveSettings {
+    pub email_webhooks: EmailWebhookSettings,
+    pub login: LoginSettings,
+    pub accounts: AccountSettings,
+}
+
+impl From<&Settings> for LiveSettings {
+    fn from(config: &Settings) -> Self {
+        Self {
+            email_webhooks: config.email_webhooks.clone(),
+            login: config.login.clone(),
+            accounts: config.accounts.clone(),
+        }
+    }
+}
+
+/// Changed configuration keys, e.g. `login.lockout_base_seconds`.
+#[derive(Debug, Serialize)]
+pub struct ReloadReport {
+    /// Now in effect.
+    pub applied: Vec<String>,
+    /// Only take effect once the application is restarted.
+    pub requires_restart: Vec<String>,
+}
+
+pub struct ConfigReloader {
+    load: LoadConfiguration,
+    live: Arc<ArcSwap<LiveSettings>>,
+    /// Unset when the application was given its email backend rather than building
+    /// it from the configuration, in which case it can't be rebuilt either.
+    email_client: Option<Arc<ReloadableEmailSender>>,
+    /// The configuration the application started with, serialized for comparison.
+    started: Value,
+    /// The configuration as last reloaded. Locked for the whole reload, so
+    /// concurrent reloads happen one after the other.
+    current: Mutex<Value>,
+}
+
+impl ConfigReloader {
+    pub fn new(
+        config: &Settings,
+        load: LoadConfiguration,
+        live: Arc<ArcSwap<LiveSettings>>,
+        email_client: Option<Arc<ReloadableEmailSender>>,
+    ) -> Result<Self> {
+        let started = serde_json::to_value(config)?;
+        Ok(Self {
+            load,
+            live,
+            email_client,
+            current: Mutex::new(started.clone()),
+            started,
+        })
+    }
+
+    fn is_reloadable(&self, key: &str) -> bool {
+        key == "application.log_level"
+            || key.starts_with("email_webhooks.")
+            || key.starts_with("login.")
+            || key.starts_with("accounts.")
+            || (key.starts_with("email_client.") && self.email_client.is_some())
+    }
+
+    /// Loads the configuration and applies what can change while running. Nothing
+    /// changes when the new configuration can't be loaded.
+    #[instrument(name = "Reloading configuration", skip(self))]
+    pub fn reload(&self) -> Result<ReloadReport> {
+        let config = (self.load)()?;
+        let new = serde_json::to_value(&config)?;
+        let mut current = self.current.lock().unwrap();
+
+        let report = ReloadReport {
+            applied: changed_keys(&current, &new)
+                .into_iter()
+                .filter(|key| self.is_reloadable(key))
+                .collect(),
+            requires_restart: changed_keys(&self.started, &new)
+                .into_iter()
+                .filter(|key| !self.is_reloadable(key))
+                .collect(),
+        };
+
+        let email_client_changed = report
+            .applied
+            .iter()
+            .any(|key| key.starts_with("email_client."));
+        let email_sender = match &self.email_client {
+            Some(_) if email_client_changed => Some(
+                build_email_sender(&config.email_client, config.email_client.sender()?)
+                    .context("Failed to build the email client")?,
+            ),
+            _ => None,
+        };
+        if report
+            .applied
+            .iter()
+            .any(|key| key == "application.log_level")
+        {
+            set_log_filter(&config.application.log_level)?;
+        }
+        if let (Some(email_client), Some(email_sender)) = (&self.email_client, email_sender) {
+            email_client.replace(email_sender);
+        }
+        self.live.store(Arc::new(LiveSettings::from(&config)));
+        *current = new;
+
+        tracing::info!(
+            applied = ?report.applied,
+            requires_restart = ?report.requires_restart,
+            "Reloaded configuration"
+        );
+        Ok(report)
+    }
+}
+
+/// The dotted keys of the leaves that differ between two serialized configurations.
+fn changed_keys(old: &Value, new: &Value) -> Vec<String> {
+    let mut changed = Vec::new();
+    collect_changed_keys("", old, new, &mut changed);
+    changed
+}
+
+fn collect_changed_keys(key: &str, old: &Value, new: &Value, changed: &mut Vec<String>) {
+    let nested = |child: &str| {
+        if key.is_empty() {
+            child.to_string()
+        } else {
+            format!("{key}.{child}")
+        }
+    };
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut children: Vec<&String> = old.keys().chain(new.keys()).collect();
+            children.sort();
+            children.dedup();
+            for child in children {
+                collect_changed_keys(
+                    &nested(child),
+                    old.get(child).unwrap_or(&Value::Null),
+                    new.get(child).unwrap_or(&Value::Null),
+                    changed,
+                );
+            }
+        }
+        (Value::Array(old), Value::Array(new)) if old.len() == new.len() => {
+            for (i, (old, new)) in old.iter().zip(new).enumerate() {
+                collect_changed_keys(&format!("{key}[{i}]"), old, new, changed);
+            }
+        }
+        (old, new) if old != new => changed.push(key.to_string()),
+        _ => {}
+    }
+}
+
+/// Reloads the configuration whenever the process receives SIGHUP, until `stopping`.
+pub async fn reload_on_sighup(
+    reloader: Arc<ConfigReloader>,
+    stopping: CancellationToken,
+) -> Result<()> {
+    let mut hangups = signal(SignalKind::hangup())?;
+    loop {
+        tokio::select! {
+            Some(()) = hangups.recv() => {}
+            _ = stopping.cancelled() => break,
+            else => break,
+        }
+        let reloader = reloader.clone();
+        if let Err(e) = spawn_blocking_with_tracing(move || reloader.reload()).await? {
+            tracing::error!("Failed to reload the configuration, keeping the current one: {e:?}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::changed_keys;
+
+    #[test]
+    fn changed_keys_are_reported_as_dotted_paths() {
+        let old = json!({
+            "application": { "port": 8000, "log_level": "info" },
+            "email_client": { "fallbacks": [{ "api_key": "a" }], "smtp": null },
+        });
+        let new = json!({
+            "application": { "port": 8000, "log_level": "debug" },
+            "email_client": { "fallbacks": [{ "api_key": "b" }], "smtp": { "host": "mx" } },
+        });
+
+        assert_eq!(
+            changed_keys(&old, &new),
+            vec![
+                "application.log_level",
+                "email_client.fallbacks[0].api_key",
+                "email_client.smtp"
+            ]
+        );
+        assert!(changed_keys(&old, &old).is_empty());
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use tracing::{error, instrument};
+
+use crate::{
+    authentication::AuthenticatedUser, startup::AppState, telemetry::spawn_blocking_with_tracing,
+};
+
+/// Reloads the configuration like SIGHUP does, responding with what changed. An
+/// invalid configuration is rejected with 422 and the current one stays in effect.
+#[instrument(name = "Reloading configuration on request", skip(state), fields(reloaded_by = %user.user_id))]
+pub async fn reload_configuration(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+) -> Response {
+    let reloader = state.reloader.clone();
+    match spawn_blocking_with_tracing(move || reloader.reload()).await {
+        Ok(Ok(report)) => Json(report).into_response(),
+        Ok(Err(e)) => {
+            error!("Failed to reload the configuration, keeping the current one: {e:?}");
+            (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({ "error": format!("{e:#}") })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use tracing::error;
+
+use crate::{
+    email_client::{read_outbox, OutboxEmail},
+    html::escape_html,
+};
+
+#[derive(Clone)]
+pub struct OutboxDirectory(pub PathBuf);
+
+/// Lists the emails captured by the outbox email backend. Only mounted outside of production.
+pub async fn dev_outbox(State(directory): State<OutboxDirectory>) -> Response {
+    match read_outbox(&directory.0).await {
+        Ok(emails) => Html(render_outbox(&directory.0, &emails)).into_response(),
+        Err(e) => {
+            error!("Failed to read outbox: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+fn render_outbox(directory: &std::path::Path, emails: &[OutboxEmail]) -> String {
+    let mut page = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="utf-8"><title>Outbox</title></head>
+<body>
+<h1>Outbox</h1>
+<p>{} emails in <code>{}</code></p>
+"#,
+        emails.len(),
+        escape_html(&directory.display().to_string())
+    );
+    for email in emails {
+        page.push_str(&format!(
+            r#"<article>
+<h2>{subject}</h2>
+<p>From <code>{from}</code> to <code>{to}</code> at {sent_at}</p>
+{details}<iframe sandbox srcdoc="{html}" style="width: 100%; height: 20em;"></iframe>
+<details><summary>Plain text</summary><pre>{text}</pre></details>
+</article>
+"#,
+            subject = escape_html(&email.subject),
+            from = escape_html(&email.from),
+            to = escape_html(&email.to),
+            sent_at = email.sent_at.to_rfc3339(),
+            details = render_details(email),
+            html = escape_html(&email.html_content),
+            text = escape_html(&email.text_content),
+        ));
+    }
+    page.push_str("</body>\n</html>\n");
+    page
+}
+
+fn render_details(email: &OutboxEmail) -> String {
+    let mut details = String::new();
+    if let Some(reply_to) = &email.reply_to {
+        details.push_str(&format!(
+            "<p>Reply to <code>{}</code></p>\n",
+            escape_html(reply_to)
+        ));
+    }
+    if !email.tags.is_empty() {
+        details.push_str(&format!(
+            "<p>Tags: {}</p>\n",
+            escape_html(&email.tags.join(", "))
+        ));
+    }
+    if !email.headers.is_empty() {
+        details.push_str("<details><summary>Headers</summary><pre>");
+        for (name, value) in &email.headers {
+            details.push_str(&escape_html(&format!("{name}: {value}\n")));
+        }
+        details.push_str("</pre></details>\n");
+    }
+    if !email.attachments.is_empty() {
+        details.push_str("<ul>\n");
+        for attachment in &email.attachments {
+            let inline = if attachment.content_id.is_some() {
+                ", inline"
+            } else {
+                ""
+            };
+            details.push_str(&format!(
+                "<li><code>{}</code> ({}{inline})</li>\n",
+                escape_html(&attachment.filename),
+                escape_html(&attachment.content_type),
+            ));
+        }
+        details.push_str("</ul>\n");
+    }
+    details
+}
@@ -1,5 +1,43 @@
-use axum::http::StatusCode;
+use std::sync::Arc;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde_json::{json, Value};
+
+use crate::{email_client::CircuitState, startup::AppState};
 
 pub async fn health_check() -> StatusCode {
     StatusCode::OK
 }
+
+/// Ready while the database is reachable and at least one email provider's circuit isn't open.
+pub async fn readiness(State(state): State<Arc<AppState>>) -> (StatusCode, Json<Value>) {
+    let database_ready = state.connection.acquire().await.is_ok();
+    let providers = state.email_client.health();
+    let email_ready = providers.is_empty()
+        || providers
+            .iter()
+            .any(|provider| provider.state != CircuitState::Open);
+
+    let status = if database_ready && email_ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    let providers: Vec<Value> = providers
+        .iter()
+        .map(|provider| {
+            json!({
+                "position": provider.position,
+                "provider": provider.provider,
+                "circuit": provider.state.as_str(),
+            })
+        })
+        .collect();
+    (
+        status,
+        Json(json!({
+            "database": if database_ready { "ok" } else { "unavailable" },
+            "email_providers": providers,
+        })),
+    )
+}
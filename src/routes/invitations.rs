@@ -0,0 +1,265 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    Form,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{error, instrument};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{compute_password_hash, AuthenticatedUser, UserRole},
+    domain::{NewPassword, SubscriberEmail, Username},
+    email_client::EmailMessage,
+    email_events::record_delivery,
+    startup::AppState,
+    suppressions::is_suppressed,
+    telemetry::spawn_blocking_with_tracing,
+    user_tokens::{
+        consume_token, pending_token_exists, store_token, NewUserToken, SignedToken, TokenKind,
+    },
+};
+
+#[derive(Deserialize, Debug)]
+pub struct InvitationFormData {
+    email: String,
+}
+
+#[instrument(name = "Inviting a new admin", skip(state, form), fields(invited_by = %user.user_id))]
+pub async fn invite_admin(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Form(form): Form<InvitationFormData>,
+) -> StatusCode {
+    if user.role != UserRole::Owner {
+        return StatusCode::FORBIDDEN;
+    }
+    let email = if let Ok(email) = SubscriberEmail::parse(form.email) {
+        email
+    } else {
+        return StatusCode::BAD_REQUEST;
+    };
+    match user_with_email_exists(&state.connection, &email).await {
+        Ok(false) => {}
+        Ok(true) => return StatusCode::CONFLICT,
+        Err(e) => {
+            error!("{e:?}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    // Only one of two invitations could be accepted, the other invitee would be stuck
+    // with an account they can't create whatever username they pick
+    match pending_token_exists(&state.connection, TokenKind::Invitation, email.as_ref()).await {
+        Ok(false) => {}
+        Ok(true) => return StatusCode::CONFLICT,
+        Err(e) => {
+            error!("{e:?}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    match is_suppressed(&state.connection, email.as_ref()).await {
+        Ok(false) => {}
+        Ok(true) => return StatusCode::UNPROCESSABLE_ENTITY,
+        Err(e) => {
+            error!("{e:?}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    match send_invitation(&state, user.user_id, email).await {
+        Ok(()) => StatusCode::OK,
+        Err(e) => {
+            error!("Failed to send invitation: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn send_invitation(state: &AppState, invited_by: Uuid, email: SubscriberEmail) -> Result<()> {
+    let settings = state.settings.load_full();
+    let token = SignedToken::generate(TokenKind::Invitation, &state.hmac_secret)?;
+    store_token(
+        &state.connection,
+        &token,
+        NewUserToken {
+            kind: TokenKind::Invitation,
+            email: email.as_ref(),
+            user_id: None,
+            created_by: Some(invited_by),
+            requested_from: None,
+            expires_at: chrono::Utc::now() + settings.accounts.invitation_expiry(),
+        },
+    )
+    .await?;
+
+    let link = format!(
+        "{}/invitations/accept?token={}",
+        state.base_url, token.token
+    );
+    let hours = settings.accounts.invitation_expiry_hours;
+    let recipient = email.as_ref().to_string();
+    let receipt = state
+        .email_client
+        .send_email(
+            email,
+            &EmailMessage::new(
+                "You have been invited to help run the newsletter",
+                format!(
+                    "You have been invited to become an admin of the newsletter.<br />\
+                    Click <a href=\"{link}\">here</a> to choose a username and password.<br />\
+                    The link expires in {hours} hours."
+                ),
+                format!(
+                    "You have been invited to become an admin of the newsletter.\n\
+                    Visit {link} to choose a username and password.\n\
+                    The link expires in {hours} hours."
+                ),
+            ),
+        )
+        .await
+        .context("Failed to send invitation email")?;
+    // The email is out, failing the request now would only get it sent twice
+    if let Err(e) = record_delivery(&state.connection, &recipient, &receipt).await {
+        error!("{e:?}");
+    }
+    Ok(())
+}
+
+#[instrument(name = "Check for existing user with email", skip(connection))]
+async fn user_with_email_exists(connection: &PgPool, email: &SubscriberEmail) -> Result<bool> {
+    let exists = sqlx::query!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE lower(email) = lower($1)) AS "exists!""#,
+        email.as_ref(),
+    )
+    .fetch_one(connection)
+    .await
+    .context("Failed to check for existing user")?
+    .exists;
+    Ok(exists)
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TokenQuery {
+    pub token: String,
+}
+
+pub async fn accept_invitation_form(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TokenQuery>,
+) -> Response {
+    // Only render tokens we signed, they can't contain anything that would need escaping
+    if SignedToken::verify(TokenKind::Invitation, &query.token, &state.hmac_secret).is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="utf-8"><title>Accept invitation</title></head>
+<body>
+<form action="/invitations/accept" method="post">
+<input type="hidden" name="token" value="{}">
+<label>Username <input type="text" name="username"></label>
+<label>Password <input type="password" name="password"></label>
+<button type="submit">Create account</button>
+</form>
+</body>
+</html>"#,
+        query.token
+    ))
+    .into_response()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AcceptInvitationFormData {
+    token: String,
+    username: String,
+    password: Secret<String>,
+}
+
+#[instrument(name = "Accepting an invitation", skip(state, form), fields(username = %form.username))]
+pub async fn accept_invitation(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<AcceptInvitationFormData>,
+) -> StatusCode {
+    let token_hash = if let Some(hash) =
+        SignedToken::verify(TokenKind::Invitation, &form.token, &state.hmac_secret)
+    {
+        hash
+    } else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    let (username, password) = match (
+        Username::parse(form.username),
+        NewPassword::parse(form.password),
+    ) {
+        (Ok(username), Ok(password)) => (username, password),
+        _ => return StatusCode::BAD_REQUEST,
+    };
+    match create_invited_user(&state.connection, &token_hash, username, password).await {
+        Ok(CreateInvitedUserOutcome::Created) => StatusCode::OK,
+        Ok(CreateInvitedUserOutcome::InvalidToken) => StatusCode::UNAUTHORIZED,
+        Ok(CreateInvitedUserOutcome::AlreadyExists) => StatusCode::CONFLICT,
+        Err(e) => {
+            error!("Failed to accept invitation: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+enum CreateInvitedUserOutcome {
+    Created,
+    InvalidToken,
+    AlreadyExists,
+}
+
+async fn create_invited_user(
+    connection: &PgPool,
+    token_hash: &str,
+    username: Username,
+    password: NewPassword,
+) -> Result<CreateInvitedUserOutcome> {
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password.into_secret()))
+            .await
+            .context("Failed to spawn blocking task")??;
+
+    let mut transaction = connection
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+    let token = match consume_token(&mut transaction, TokenKind::Invitation, token_hash).await? {
+        Some(token) => token,
+        None => return Ok(CreateInvitedUserOutcome::InvalidToken),
+    };
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, email, role)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        Uuid::new_v4(),
+        username.as_ref(),
+        password_hash.expose_secret(),
+        token.email,
+        UserRole::Admin.as_str(),
+    )
+    .execute(&mut transaction)
+    .await;
+    match inserted {
+        Ok(_) => {}
+        // The token stays unused when the transaction is rolled back, so the invitee can pick another username
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("23505") => {
+            return Ok(CreateInvitedUserOutcome::AlreadyExists)
+        }
+        Err(e) => return Err(e).context("Failed to insert invited user"),
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(CreateInvitedUserOutcome::Created)
+}
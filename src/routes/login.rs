@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    http::{header::RETRY_AFTER, StatusCode},
+    response::{IntoResponse, Response},
+    Form,
+};
+use secrecy::Secret;
+use serde::Deserialize;
+use tracing::{error, instrument};
+
+use crate::{
+    authentication::{validate_credentials, AuthError, Credentials},
+    client_ip::ClientIp,
+    startup::AppState,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct LoginFormData {
+    username: String,
+    password: Secret<String>,
+}
+
+#[instrument(
+    name = "Logging in",
+    skip(state, form),
+    fields(username = %form.username, user_id = tracing::field::Empty)
+)]
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+    Form(form): Form<LoginFormData>,
+) -> Response {
+    let credentials = Credentials {
+        username: form.username,
+        password: form.password,
+    };
+    match validate_credentials(
+        &state.connection,
+        &state.settings.load().login,
+        credentials,
+        ip,
+    )
+    .await
+    {
+        Ok(user_id) => {
+            tracing::Span::current().record("user_id", tracing::field::display(user_id));
+            StatusCode::OK.into_response()
+        }
+        Err(AuthError::InvalidCredentials) => StatusCode::UNAUTHORIZED.into_response(),
+        Err(AuthError::LockedOut { retry_after }) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, retry_after.as_secs().max(1).to_string())],
+        )
+            .into_response(),
+        Err(AuthError::Unexpected(e)) => {
+            error!("Failed to validate credentials: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
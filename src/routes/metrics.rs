@@ -0,0 +1,31 @@
+use std::{fmt::Write, sync::Arc};
+
+use axum::{extract::State, http::header, response::IntoResponse};
+
+use crate::{email_client::CircuitState, startup::AppState};
+
+/// Prometheus text exposition of the email provider circuit breakers.
+pub async fn metrics(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let mut body = String::from(
+        "# HELP email_provider_circuit_state Circuit breaker state of each email provider.\n\
+        # TYPE email_provider_circuit_state gauge\n",
+    );
+    for provider in state.email_client.health() {
+        for circuit in [
+            CircuitState::Closed,
+            CircuitState::HalfOpen,
+            CircuitState::Open,
+        ] {
+            writeln!(
+                body,
+                "email_provider_circuit_state{{position=\"{}\",provider=\"{}\",state=\"{}\"}} {}",
+                provider.position,
+                provider.provider,
+                circuit.as_str(),
+                u8::from(provider.state == circuit)
+            )
+            .expect("Writing to a String can't fail");
+        }
+    }
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], body)
+}
@@ -1,5 +1,21 @@
+mod config;
+mod dev_outbox;
 mod health_check;
+mod invitations;
+mod login;
+mod metrics;
+mod password_reset;
 mod subscriptions;
+mod suppressions;
+mod webhooks;
 
+pub use config::*;
+pub use dev_outbox::*;
 pub use health_check::*;
+pub use invitations::*;
+pub use login::*;
+pub use metrics::*;
+pub use password_reset::*;
 pub use subscriptions::*;
+pub use suppressions::*;
+pub use webhooks::*;
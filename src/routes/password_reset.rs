@@ -0,0 +1,258 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    Form,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::{error, instrument, warn, Instrument};
+use uuid::Uuid;
+
+use crate::{
+    authentication::{compute_password_hash, reset_account_lockout},
+    client_ip::ClientIp,
+    domain::{NewPassword, SubscriberEmail},
+    email_client::{EmailMessage, SendEmailError},
+    email_events::record_delivery,
+    routes::TokenQuery,
+    startup::AppState,
+    telemetry::spawn_blocking_with_tracing,
+    user_tokens::{
+        consume_token, recent_token_count, store_token, NewUserToken, SignedToken, TokenKind,
+    },
+};
+
+#[derive(Deserialize, Debug)]
+pub struct PasswordResetFormData {
+    email: String,
+}
+
+/// Responds the same way, and as fast, whether or not an account exists for the
+/// address, and whether or not the request was rate limited.
+#[instrument(name = "Requesting a password reset", skip(state, form))]
+pub async fn request_password_reset(
+    State(state): State<Arc<AppState>>,
+    ClientIp(ip): ClientIp,
+    Form(form): Form<PasswordResetFormData>,
+) -> StatusCode {
+    let email = if let Ok(email) = SubscriberEmail::parse(form.email) {
+        email
+    } else {
+        return StatusCode::BAD_REQUEST;
+    };
+    // Only known addresses get a token and an email, waiting for that would tell them apart
+    let send = {
+        let state = state.clone();
+        async move {
+            if let Err(e) = send_password_reset(&state, email, &ip.to_string()).await {
+                error!("Failed to send password reset: {e:?}");
+            }
+        }
+    };
+    state
+        .shutdown
+        .spawn_task(send.instrument(tracing::Span::current()));
+    StatusCode::OK
+}
+
+async fn send_password_reset(
+    state: &AppState,
+    email: SubscriberEmail,
+    requested_from: &str,
+) -> Result<()> {
+    let settings = state.settings.load_full();
+    let recent_requests = recent_token_count(
+        &state.connection,
+        TokenKind::PasswordReset,
+        email.as_ref(),
+        requested_from,
+    )
+    .await?;
+    if recent_requests >= settings.accounts.password_reset_requests_per_hour {
+        warn!("Rate limiting password reset requests for {email:?} from {requested_from}");
+        return Ok(());
+    }
+    let user_id = match get_user_id_by_email(&state.connection, &email).await? {
+        Some(user_id) => user_id,
+        None => return Ok(()),
+    };
+
+    let token = SignedToken::generate(TokenKind::PasswordReset, &state.hmac_secret)?;
+    store_token(
+        &state.connection,
+        &token,
+        NewUserToken {
+            kind: TokenKind::PasswordReset,
+            email: email.as_ref(),
+            user_id: Some(user_id),
+            created_by: None,
+            requested_from: Some(requested_from),
+            expires_at: chrono::Utc::now() + settings.accounts.password_reset_expiry(),
+        },
+    )
+    .await?;
+
+    let link = format!(
+        "{}/password_reset/confirm?token={}",
+        state.base_url, token.token
+    );
+    let minutes = settings.accounts.password_reset_expiry_minutes;
+    let recipient = email.as_ref().to_string();
+    let sent = state
+        .email_client
+        .send_email(
+            email,
+            &EmailMessage::new(
+                "Reset your password",
+                format!(
+                    "Someone requested a password reset for your newsletter admin account.<br />\
+                    Click <a href=\"{link}\">here</a> to choose a new password.<br />\
+                    The link expires in {minutes} minutes. If you didn't request this, you can ignore this email."
+                ),
+                format!(
+                    "Someone requested a password reset for your newsletter admin account.\n\
+                    Visit {link} to choose a new password.\n\
+                    The link expires in {minutes} minutes. If you didn't request this, you can ignore this email."
+                ),
+            ),
+        )
+        .await;
+    let receipt = match sent {
+        Ok(receipt) => receipt,
+        // Answered like any other request, so the form doesn't reveal who is suppressed
+        Err(e @ SendEmailError::Suppressed(_)) => {
+            warn!("{e}");
+            return Ok(());
+        }
+        Err(e) => return Err(e).context("Failed to send password reset email"),
+    };
+    // The email is out, failing the request now would only get it sent twice
+    if let Err(e) = record_delivery(&state.connection, &recipient, &receipt).await {
+        error!("{e:?}");
+    }
+    Ok(())
+}
+
+#[instrument(name = "Get user id by email", skip(connection))]
+async fn get_user_id_by_email(
+    connection: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<Option<Uuid>> {
+    let user_id = sqlx::query!(
+        r#"SELECT user_id FROM users WHERE lower(email) = lower($1)"#,
+        email.as_ref(),
+    )
+    .fetch_optional(connection)
+    .await
+    .context("Failed to retrieve user by email")?
+    .map(|row| row.user_id);
+    Ok(user_id)
+}
+
+pub async fn confirm_password_reset_form(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TokenQuery>,
+) -> Response {
+    // Only render tokens we signed, they can't contain anything that would need escaping
+    if SignedToken::verify(TokenKind::PasswordReset, &query.token, &state.hmac_secret).is_none() {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    Html(format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="utf-8"><title>Reset password</title></head>
+<body>
+<form action="/password_reset/confirm" method="post">
+<input type="hidden" name="token" value="{}">
+<label>New password <input type="password" name="password"></label>
+<button type="submit">Reset password</button>
+</form>
+</body>
+</html>"#,
+        query.token
+    ))
+    .into_response()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConfirmPasswordResetFormData {
+    token: String,
+    password: Secret<String>,
+}
+
+#[instrument(name = "Resetting a password", skip(state, form))]
+pub async fn confirm_password_reset(
+    State(state): State<Arc<AppState>>,
+    Form(form): Form<ConfirmPasswordResetFormData>,
+) -> StatusCode {
+    let token_hash = if let Some(hash) =
+        SignedToken::verify(TokenKind::PasswordReset, &form.token, &state.hmac_secret)
+    {
+        hash
+    } else {
+        return StatusCode::UNAUTHORIZED;
+    };
+    let password = if let Ok(password) = NewPassword::parse(form.password) {
+        password
+    } else {
+        return StatusCode::BAD_REQUEST;
+    };
+    match reset_password(&state.connection, &token_hash, password).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::UNAUTHORIZED,
+        Err(e) => {
+            error!("Failed to reset password: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Returns whether the token was valid and the password has been changed.
+async fn reset_password(
+    connection: &PgPool,
+    token_hash: &str,
+    password: NewPassword,
+) -> Result<bool> {
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password.into_secret()))
+            .await
+            .context("Failed to spawn blocking task")??;
+
+    let mut transaction = connection
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+    let token = match consume_token(&mut transaction, TokenKind::PasswordReset, token_hash).await? {
+        Some(token) => token,
+        None => return Ok(false),
+    };
+    let user_id = token
+        .user_id
+        .context("Password reset token is not associated with a user")?;
+    let username = sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $2
+        WHERE user_id = $1
+        RETURNING username
+        "#,
+        user_id,
+        password_hash.expose_secret(),
+    )
+    .fetch_one(&mut transaction)
+    .await
+    .context("Failed to update password")?
+    .username;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    reset_account_lockout(connection, &username).await?;
+    Ok(true)
+}
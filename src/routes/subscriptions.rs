@@ -5,12 +5,13 @@ use axum::{extract::State, http::StatusCode, Form};
 use chrono::Utc;
 use serde::Deserialize;
 use sqlx::PgPool;
-use tracing::{error, instrument};
+use tracing::{error, info, instrument};
 use uuid::Uuid;
 
 use crate::{
     domain::{NewSubscriber, SubscriberEmail, SubscriberName},
     startup::AppState,
+    suppressions::is_suppressed,
 };
 
 #[derive(Deserialize, Debug)]
@@ -34,11 +35,23 @@ pub async fn subscribe(
     State(state): State<Arc<AppState>>,
     Form(form): Form<FormData>,
 ) -> StatusCode {
-    let new_subscriber = if let Ok(new_sub) = form.try_into() {
+    let new_subscriber: NewSubscriber = if let Ok(new_sub) = form.try_into() {
         new_sub
     } else {
         return StatusCode::BAD_REQUEST;
     };
+    match is_suppressed(&state.connection, new_subscriber.email.as_ref()).await {
+        Ok(false) => {}
+        // Looks like any other subscription, so the form doesn't reveal who bounced or complained
+        Ok(true) => {
+            info!("Not subscribing suppressed address");
+            return StatusCode::OK;
+        }
+        Err(e) => {
+            error!("{e:?}");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
     match insert_subscriber(&state.connection, &new_subscriber).await {
         Ok(_) => StatusCode::OK,
         Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Form, Json,
+};
+use serde::Deserialize;
+use tracing::{error, instrument};
+
+use crate::{
+    authentication::AuthenticatedUser,
+    domain::SubscriberEmail,
+    startup::AppState,
+    suppressions::{search_suppressions, suppress, unsuppress, SuppressionReason},
+};
+
+const SEARCH_LIMIT: i64 = 100;
+
+#[derive(Deserialize, Debug)]
+pub struct SuppressionSearchQuery {
+    #[serde(default)]
+    search: String,
+}
+
+/// Lists suppressed addresses containing `search`, newest first.
+#[instrument(name = "Searching suppressions", skip(state, _user))]
+pub async fn list_suppressions(
+    State(state): State<Arc<AppState>>,
+    _user: AuthenticatedUser,
+    Query(query): Query<SuppressionSearchQuery>,
+) -> Response {
+    match search_suppressions(&state.connection, &query.search, SEARCH_LIMIT).await {
+        Ok(suppressions) => Json(suppressions).into_response(),
+        Err(e) => {
+            error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SuppressionFormData {
+    email: String,
+    note: Option<String>,
+}
+
+#[instrument(name = "Suppressing an address", skip(state), fields(added_by = %user.user_id))]
+pub async fn add_suppression(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Form(form): Form<SuppressionFormData>,
+) -> StatusCode {
+    let email = if let Ok(email) = SubscriberEmail::parse(form.email) {
+        email
+    } else {
+        return StatusCode::BAD_REQUEST;
+    };
+    let note = form.note.filter(|note| !note.trim().is_empty());
+    match suppress(
+        &state.connection,
+        email.as_ref(),
+        SuppressionReason::Manual,
+        &format!("admin:{}", user.user_id),
+        note.as_deref(),
+    )
+    .await
+    {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::CONFLICT,
+        Err(e) => {
+            error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[instrument(name = "Unsuppressing an address", skip(state), fields(removed_by = %user.user_id))]
+pub async fn remove_suppression(
+    State(state): State<Arc<AppState>>,
+    user: AuthenticatedUser,
+    Path(email): Path<String>,
+) -> StatusCode {
+    match unsuppress(&state.connection, &email).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            error!("{e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
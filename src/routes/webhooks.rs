@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use secrecy::Secret;
+use serde::Deserialize;
+use tracing::{error, instrument, warn};
+
+use crate::{
+    email_events::{parse_elastic_email_notification, record_event, verify_token},
+    startup::AppState,
+};
+
+#[derive(Deserialize)]
+pub struct WebhookQuery {
+    token: Option<Secret<String>>,
+}
+
+/// Receives delivery, bounce and complaint notifications from an email provider,
+/// which is given a notification URL with the configured token as `?token=`.
+#[instrument(name = "Receiving an email webhook", skip(state, query, body))]
+pub async fn email_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(provider): Path<String>,
+    Query(query): Query<WebhookQuery>,
+    body: Bytes,
+) -> StatusCode {
+    let parse = match provider.as_str() {
+        "elastic_email" => parse_elastic_email_notification,
+        _ => return StatusCode::NOT_FOUND,
+    };
+    let token = query.token.unwrap_or_else(|| Secret::new(String::new()));
+    if !verify_token(&token, &state.settings.load().email_webhooks.token) {
+        warn!("Rejecting {provider} webhook with an invalid token");
+        return StatusCode::UNAUTHORIZED;
+    }
+    let event = match parse(&body) {
+        Ok(Some(event)) => event,
+        Ok(None) => return StatusCode::OK,
+        Err(e) => {
+            warn!("Failed to parse {provider} webhook: {e:?}");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    match record_event(
+        &state.connection,
+        &provider,
+        &event,
+        &body,
+        state.settings.load().email_webhooks.soft_bounce_threshold,
+    )
+    .await
+    {
+        Ok(_) => StatusCode::OK,
+        Err(e) => {
+            error!("Failed to record {provider} webhook: {e:?}");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
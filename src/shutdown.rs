@@ -0,0 +1,72 @@
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    task::JoinHandle,
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+
+/// Tells background workers to stop, and waits for them and for the tasks requests
+/// left running to finish what they are doing.
+#[derive(Clone, Default)]
+pub struct Shutdown {
+    stopping: CancellationToken,
+    workers: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+impl Shutdown {
+    /// A worker should finish its current task and return once this resolves.
+    pub fn stopping(&self) -> CancellationToken {
+        self.stopping.clone()
+    }
+
+    pub fn spawn(&self, worker: impl Future<Output = ()> + Send + 'static) {
+        self.workers.lock().unwrap().push(tokio::spawn(worker));
+    }
+
+    /// Runs work a request started but doesn't wait for, e.g. sending an email.
+    pub fn spawn_task(&self, task: impl Future<Output = ()> + Send + 'static) {
+        let mut tasks = self.tasks.lock().unwrap();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(tokio::spawn(task));
+    }
+
+    /// Waits for the tasks spawned so far.
+    pub async fn wait_for_tasks(&self) {
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        for task in tasks {
+            let _ = task.await;
+        }
+    }
+
+    /// Signals the workers and waits for them and the tasks until `deadline`, then
+    /// aborts the stragglers.
+    pub async fn stop(&self, deadline: Instant) {
+        self.stopping.cancel();
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap());
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+        for mut worker in tasks.into_iter().chain(workers) {
+            if tokio::time::timeout_at(deadline, &mut worker)
+                .await
+                .is_err()
+            {
+                tracing::warn!("A background task did not stop in time, aborting it");
+                worker.abort();
+            }
+        }
+    }
+}
+
+/// Resolves on SIGTERM, as sent by container runtimes, or Ctrl-C.
+pub async fn terminate_or_interrupt() {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = terminate.recv() => tracing::info!("Received SIGTERM"),
+        _ = tokio::signal::ctrl_c() => tracing::info!("Received Ctrl-C"),
+    }
+}
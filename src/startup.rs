@@ -1,89 +1,319 @@
-use std::{net::TcpListener, sync::Arc};
+use std::{
+    future::Future,
+    net::{SocketAddr, TcpListener},
+    path::PathBuf,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use secrecy::Secret;
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use tower_http::{
-    request_id::MakeRequestUuid,
-    trace::{DefaultMakeSpan, TraceLayer},
-    ServiceBuilderExt,
-};
+use tokio_util::sync::CancellationToken;
+use tower_http::{request_id::MakeRequestUuid, trace::TraceLayer, ServiceBuilderExt};
 
 use crate::{
-    configuration::{DatabaseSettings, Settings},
-    email_client::EmailClient,
+    configuration::{ClientIpSettings, DatabaseSettings, EmailProvider, Environment, Settings},
+    email_client::{build_email_sender, EmailSender, ReloadableEmailSender},
+    reload::{ConfigReloader, LiveSettings, LoadConfiguration},
     routes::*,
+    shutdown::{terminate_or_interrupt, Shutdown},
+    suppressions::SuppressingEmailSender,
+    telemetry::make_request_span,
+    tls::{load_tls_config, redirect_to_https},
 };
 
-type Server =
-    axum::Server<hyper::server::conn::AddrIncoming, axum::routing::IntoMakeService<Router>>;
+type Server = Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>>;
 
 pub struct App {
     port: u16,
+    redirect_port: Option<u16>,
     server: Server,
+    handle: Handle,
+    shutdown: Shutdown,
+    shutdown_timeout: Duration,
+    connection_pool: PgPool,
+    reloader: Arc<ConfigReloader>,
 }
 
 impl App {
-    pub async fn build(config: &Settings) -> Result<Self> {
-        let connection_pool = get_connection_pool(&config.database);
-        let sender_email = config.email_client.sender().expect("Invalid sender email");
-        let email_client = EmailClient::new(
-            config.email_client.base_url.clone(),
+    /// Builds the application from `config`, reloading it with `load`.
+    pub async fn build(config: &Settings, load: LoadConfiguration) -> Result<Self> {
+        let sender_email = config.email_client.sender()?;
+        let email_client = Arc::new(ReloadableEmailSender::new(build_email_sender(
+            &config.email_client,
             sender_email,
-            config.email_client.api_key.clone(),
-            config.email_client.timeout(),
-        );
+        )?));
+        Self::build_inner(config, email_client.clone(), Some(email_client), load).await
+    }
+
+    /// Like `build`, but with the given email backend instead of the configured one.
+    pub async fn build_with_email_sender(
+        config: &Settings,
+        email_client: Arc<dyn EmailSender>,
+        load: LoadConfiguration,
+    ) -> Result<Self> {
+        Self::build_inner(config, email_client, None, load).await
+    }
+
+    async fn build_inner(
+        config: &Settings,
+        email_client: Arc<dyn EmailSender>,
+        reloadable_email_client: Option<Arc<ReloadableEmailSender>>,
+        load: LoadConfiguration,
+    ) -> Result<Self> {
+        let connection_pool = get_connection_pool(&config.database);
+        let shutdown = Shutdown::default();
+        shutdown.spawn(log_pool_saturation(
+            connection_pool.clone(),
+            config.database.pool.max_connections,
+            shutdown.stopping(),
+        ));
 
         let listener = TcpListener::bind(format!(
             "{}:{}",
             config.application.host, config.application.port
         ))?;
         let port = listener.local_addr()?.port();
-        let server = run(listener, connection_pool, email_client)?;
+        let tls = match &config.application.tls {
+            Some(tls) => Some(load_tls_config(tls, &shutdown).await?),
+            None => None,
+        };
+        let email_client = Arc::new(SuppressingEmailSender::new(
+            email_client,
+            connection_pool.clone(),
+        ));
+        let handle = Handle::new();
+        let settings = Arc::new(ArcSwap::from_pointee(LiveSettings::from(config)));
+        let reloader = Arc::new(ConfigReloader::new(
+            config,
+            load,
+            settings.clone(),
+            reloadable_email_client,
+        )?);
+        let state = AppState {
+            connection: connection_pool.clone(),
+            email_client,
+            base_url: config.application.base_url.clone(),
+            hmac_secret: config.application.hmac_secret.clone(),
+            client_ip: config.application.client_ip.clone(),
+            shutdown: shutdown.clone(),
+            settings,
+            reloader: reloader.clone(),
+        };
+        let outbox = if config.environment != Environment::Production
+            && config.email_client.uses(EmailProvider::Outbox)
+        {
+            Some(config.email_client.outbox_directory()?)
+        } else {
+            None
+        };
+        let mut server = run(listener, state, outbox, tls, handle.clone())?;
 
-        Ok(Self { port, server })
+        let redirect_port = match config
+            .application
+            .tls
+            .as_ref()
+            .and_then(|tls| tls.redirect_port)
+        {
+            Some(redirect_port) => {
+                let listener =
+                    TcpListener::bind(format!("{}:{}", config.application.host, redirect_port))?;
+                let redirect_port = listener.local_addr()?.port();
+                let redirect = redirect_to_https(listener, port, handle.clone());
+                server = Box::pin(async move {
+                    tokio::try_join!(server, redirect)?;
+                    Ok(())
+                });
+                Some(redirect_port)
+            }
+            None => None,
+        };
+
+        Ok(Self {
+            port,
+            redirect_port,
+            server,
+            handle,
+            shutdown,
+            shutdown_timeout: config.application.shutdown_timeout(),
+            connection_pool,
+            reloader,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
 
+    /// The port of the listener redirecting to HTTPS, if there is one.
+    pub fn redirect_port(&self) -> Option<u16> {
+        self.redirect_port
+    }
+
+    pub fn reloader(&self) -> Arc<ConfigReloader> {
+        self.reloader.clone()
+    }
+
+    /// Coordinates the background workers, e.g. to spawn more of them.
+    pub fn shutdown(&self) -> Shutdown {
+        self.shutdown.clone()
+    }
+
+    /// Serves until SIGTERM or Ctrl-C, then shuts down gracefully.
     pub async fn run_until_stopped(self) -> Result<()> {
-        self.server.await.map_err(|e| anyhow::Error::from(e))
+        self.run_until(terminate_or_interrupt()).await
+    }
+
+    /// Serves until `signal` resolves. Then stops accepting connections, gives in-flight
+    /// requests and background workers until the shutdown timeout to finish, and closes
+    /// the connection pool.
+    pub async fn run_until(self, signal: impl Future<Output = ()>) -> Result<()> {
+        let mut server = tokio::spawn(self.server);
+        tokio::select! {
+            result = &mut server => return Ok(result??),
+            _ = signal => {}
+        }
+        tracing::info!(
+            timeout_seconds = self.shutdown_timeout.as_secs(),
+            "Shutting down, draining in-flight requests"
+        );
+        let deadline = tokio::time::Instant::now() + self.shutdown_timeout;
+        // Connections still open at the deadline are closed, cancelling their requests
+        self.handle.graceful_shutdown(Some(self.shutdown_timeout));
+        server.await??;
+        self.shutdown.stop(deadline).await;
+        self.connection_pool.close().await;
+        tracing::info!("Shut down");
+        Ok(())
     }
 }
 
 pub struct AppState {
     pub connection: PgPool,
-    pub email_client: EmailClient,
+    pub email_client: Arc<dyn EmailSender>,
+    pub base_url: String,
+    pub hmac_secret: Secret<String>,
+    pub client_ip: ClientIpSettings,
+    pub shutdown: Shutdown,
+    /// Replaced when the configuration is reloaded.
+    pub settings: Arc<ArcSwap<LiveSettings>>,
+    pub reloader: Arc<ConfigReloader>,
 }
 
-pub fn run(listener: TcpListener, connection: PgPool, email_client: EmailClient) -> Result<Server> {
-    let state = AppState {
-        connection,
-        email_client,
-    };
-    let app = Router::new()
+/// Serves the application on `listener`, over HTTPS when given a `tls` configuration.
+/// Lists the emails in `outbox` at `/dev/outbox` when given one.
+pub fn run(
+    listener: TcpListener,
+    state: AppState,
+    outbox: Option<PathBuf>,
+    tls: Option<RustlsConfig>,
+    handle: Handle,
+) -> Result<Server> {
+    let mut app = Router::new()
         .route("/health_check", get(health_check))
+        .route("/health_check/ready", get(readiness))
+        .route("/metrics", get(metrics))
+        .route("/admin/config/reload", post(reload_configuration))
+        .route("/admin/invitations", post(invite_admin))
+        .route(
+            "/admin/suppressions",
+            get(list_suppressions).post(add_suppression),
+        )
+        .route("/admin/suppressions/:email", delete(remove_suppression))
+        .route(
+            "/invitations/accept",
+            get(accept_invitation_form).post(accept_invitation),
+        )
+        .route("/login", post(login))
+        .route("/password_reset", post(request_password_reset))
+        .route(
+            "/password_reset/confirm",
+            get(confirm_password_reset_form).post(confirm_password_reset),
+        )
         .route("/subscriptions", post(subscribe))
-        .with_state(Arc::new(state))
-        .layer(
-            tower::ServiceBuilder::new()
-                .set_x_request_id(MakeRequestUuid)
-                .layer(
-                    TraceLayer::new_for_http()
-                        .make_span_with(DefaultMakeSpan::new().include_headers(true)),
-                )
-                .propagate_x_request_id(),
+        .route("/webhooks/email/:provider", post(email_webhook));
+    if let Some(outbox) = outbox {
+        app = app.merge(
+            Router::new()
+                .route("/dev/outbox", get(dev_outbox))
+                .with_state(OutboxDirectory(outbox)),
         );
+    }
+    let app = app.with_state(Arc::new(state)).layer(
+        tower::ServiceBuilder::new()
+            .set_x_request_id(MakeRequestUuid)
+            .layer(TraceLayer::new_for_http().make_span_with(make_request_span))
+            .propagate_x_request_id(),
+    );
 
     tracing::info!("listening on {}", listener.local_addr()?);
-    Ok(axum::Server::from_tcp(listener)?.serve(app.into_make_service()))
+    let app = app.into_make_service_with_connect_info::<SocketAddr>();
+    Ok(match tls {
+        Some(tls) => Box::pin(
+            axum_server::from_tcp_rustls(listener, tls)
+                .handle(handle)
+                .serve(app),
+        ),
+        None => Box::pin(axum_server::from_tcp(listener).handle(handle).serve(app)),
+    })
 }
 
 pub fn get_connection_pool(config: &DatabaseSettings) -> PgPool {
-    PgPoolOptions::new().connect_lazy_with(config.with_db())
+    for parameter in &config.ignored_url_parameters {
+        tracing::warn!(
+            "Ignoring the {parameter} parameter of database.url, sqlx doesn't support it"
+        );
+    }
+    PgPoolOptions::new()
+        .max_connections(config.pool.max_connections)
+        .min_connections(config.pool.min_connections)
+        .acquire_timeout(config.pool.acquire_timeout())
+        .idle_timeout(config.pool.idle_timeout())
+        .connect_lazy_with(config.with_db())
+}
+
+/// Applies the migrations embedded at build time that the database is missing.
+pub async fn run_migrations(connection: &PgPool) -> Result<()> {
+    sqlx::migrate!("./migrations")
+        .run(connection)
+        .await
+        .context("Failed to migrate the database")
+}
+
+const POOL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Warns while every connection of the pool is in use, as requests then queue up
+/// waiting for one and fail after the acquire timeout.
+async fn log_pool_saturation(pool: PgPool, max_connections: u32, stopping: CancellationToken) {
+    let mut interval = tokio::time::interval(POOL_CHECK_INTERVAL);
+    let mut saturated_since: Option<Instant> = None;
+    while !pool.is_closed() {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = stopping.cancelled() => break,
+        }
+        let saturated = pool.size() >= max_connections && pool.num_idle() == 0;
+        match (saturated, saturated_since) {
+            (true, None) => {
+                tracing::warn!(max_connections, "Database connection pool is saturated");
+                saturated_since = Some(Instant::now());
+            }
+            (false, Some(since)) => {
+                tracing::info!(
+                    saturated_for_seconds = since.elapsed().as_secs(),
+                    "Database connection pool is no longer saturated"
+                );
+                saturated_since = None;
+            }
+            _ => {}
+        }
+    }
 }
@@ -0,0 +1,131 @@
+use std::io::{Read, Write};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tracing::instrument;
+use uuid::Uuid;
+
+use crate::{
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    suppressions::suppressed_among,
+};
+
+#[derive(Serialize)]
+struct ExportedSubscriber {
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+/// Writes every subscriber as CSV with an `email,name,status,subscribed_at` header.
+#[instrument(name = "Exporting subscribers", skip_all)]
+pub async fn export_subscribers(connection: &PgPool, writer: impl Write) -> Result<u64> {
+    let subscribers = sqlx::query_as!(
+        ExportedSubscriber,
+        r#"SELECT email, name, status, subscribed_at FROM subscriptions ORDER BY subscribed_at, email"#
+    )
+    .fetch_all(connection)
+    .await
+    .context("Failed to fetch subscribers")?;
+    let mut writer = csv::Writer::from_writer(writer);
+    for subscriber in &subscribers {
+        writer.serialize(subscriber)?;
+    }
+    writer.flush()?;
+    Ok(subscribers.len() as u64)
+}
+
+#[derive(Deserialize)]
+struct ImportedSubscriber {
+    email: String,
+    name: String,
+    /// Missing for lists from elsewhere, which are taken as confirmed.
+    status: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub imported: u64,
+    pub already_subscribed: u64,
+    pub suppressed: u64,
+    /// Pending, bounced and complained subscribers, which aren't imported.
+    pub not_confirmed: u64,
+    /// Rows that could not be imported, by line number, with the reason.
+    pub invalid: Vec<(u64, String)>,
+}
+
+/// Subscribes everyone in a CSV with `email` and `name` columns, as confirmed.
+/// Rows whose `status` is anything but `confirmed` are skipped, so importing an
+/// export doesn't bring bounced or complained addresses back. Other columns are
+/// ignored. Suppressed addresses are skipped, and nothing is imported if the file
+/// can't be read.
+#[instrument(name = "Importing subscribers", skip_all)]
+pub async fn import_subscribers(connection: &PgPool, reader: impl Read) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let mut subscribers = Vec::new();
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader
+        .headers()
+        .context("Failed to read the CSV header")?
+        .clone();
+    for record in reader.records() {
+        let record = record.context("Failed to read the CSV")?;
+        let line = record.position().map_or(0, |position| position.line());
+        let parsed = record
+            .deserialize::<ImportedSubscriber>(Some(&headers))
+            .map_err(anyhow::Error::from)
+            .and_then(|row| {
+                let confirmed = row.status.as_deref().is_none_or(|s| s == "confirmed");
+                let subscriber = NewSubscriber {
+                    email: SubscriberEmail::parse(row.email)?,
+                    name: SubscriberName::parse(row.name)?,
+                };
+                Ok((subscriber, confirmed))
+            });
+        match parsed {
+            Ok((subscriber, true)) => subscribers.push(subscriber),
+            Ok((_, false)) => report.not_confirmed += 1,
+            Err(e) => report.invalid.push((line, format!("{e:#}"))),
+        }
+    }
+
+    let emails: Vec<&str> = subscribers.iter().map(|s| s.email.as_ref()).collect();
+    let suppressed = suppressed_among(connection, &emails).await?;
+    let mut transaction = connection
+        .begin()
+        .await
+        .context("Failed to begin transaction")?;
+    for subscriber in &subscribers {
+        if suppressed.contains(subscriber.email.as_ref()) {
+            report.suppressed += 1;
+            continue;
+        }
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+            VALUES ($1, $2, $3, $4, 'confirmed')
+            ON CONFLICT (email) DO NOTHING
+            "#,
+            Uuid::new_v4(),
+            subscriber.email.as_ref(),
+            subscriber.name.as_ref(),
+            Utc::now()
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to insert subscriber")?
+        .rows_affected();
+        match inserted {
+            0 => report.already_subscribed += 1,
+            _ => report.imported += 1,
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(report)
+}
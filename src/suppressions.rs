@@ -0,0 +1,231 @@
+use std::{collections::HashSet, sync::Arc};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{PgExecutor, PgPool};
+use tracing::instrument;
+
+use crate::{
+    domain::SubscriberEmail,
+    email_client::{
+        DeliveryReceipt, EmailMessage, EmailSender, ProviderHealth, RecipientOutcome,
+        SendEmailError,
+    },
+};
+
+/// Why an address must never be emailed again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuppressionReason {
+    HardBounce,
+    /// Soft bounced too many times in a row.
+    SoftBounce,
+    Complaint,
+    /// Blocked by an admin.
+    Manual,
+}
+
+impl SuppressionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuppressionReason::HardBounce => "hard_bounce",
+            SuppressionReason::SoftBounce => "soft_bounce",
+            SuppressionReason::Complaint => "complaint",
+            SuppressionReason::Manual => "manual",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Suppression {
+    pub email: String,
+    pub reason: String,
+    /// What added the entry, e.g. `webhook:elastic_email` or `admin:<user id>`.
+    pub source: String,
+    pub note: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+fn normalize(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Adds an address to the suppression list. An address that is already on it keeps
+/// its original reason and source. Returns whether the address was added.
+#[instrument(name = "Suppress email address", skip(executor))]
+pub async fn suppress(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+    reason: SuppressionReason,
+    source: &str,
+    note: Option<&str>,
+) -> Result<bool> {
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, reason, source, note, created_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (email) DO NOTHING
+        "#,
+        normalize(email),
+        reason.as_str(),
+        source,
+        note,
+        Utc::now(),
+    )
+    .execute(executor)
+    .await
+    .context("Failed to suppress email address")?
+    .rows_affected()
+        == 1;
+    Ok(inserted)
+}
+
+/// Takes an address off the suppression list. Returns whether it was on it.
+#[instrument(name = "Unsuppress email address", skip(connection))]
+pub async fn unsuppress(connection: &PgPool, email: &str) -> Result<bool> {
+    let deleted = sqlx::query!(
+        r#"DELETE FROM suppressions WHERE email = $1"#,
+        normalize(email),
+    )
+    .execute(connection)
+    .await
+    .context("Failed to unsuppress email address")?
+    .rows_affected()
+        == 1;
+    Ok(deleted)
+}
+
+#[instrument(name = "Check email address suppression", skip(connection))]
+pub async fn is_suppressed(connection: &PgPool, email: &str) -> Result<bool> {
+    Ok(!suppressed_among(connection, &[email]).await?.is_empty())
+}
+
+/// The addresses out of `emails` that are suppressed, as given. For checking a
+/// whole batch, e.g. a send or an import, with one query.
+#[instrument(name = "Check email address suppressions", skip_all)]
+pub async fn suppressed_among<'a>(
+    connection: &PgPool,
+    emails: &[&'a str],
+) -> Result<HashSet<&'a str>> {
+    let normalized: Vec<String> = emails.iter().map(|email| normalize(email)).collect();
+    let suppressed: HashSet<String> = sqlx::query!(
+        r#"SELECT email FROM suppressions WHERE email = ANY($1)"#,
+        &normalized,
+    )
+    .fetch_all(connection)
+    .await
+    .context("Failed to check email address suppressions")?
+    .into_iter()
+    .map(|row| row.email)
+    .collect();
+    Ok(emails
+        .iter()
+        .zip(&normalized)
+        .filter(|(_, normalized)| suppressed.contains(*normalized))
+        .map(|(email, _)| *email)
+        .collect())
+}
+
+/// Entries whose address contains `query`, newest first.
+#[instrument(name = "Search suppressions", skip(connection))]
+pub async fn search_suppressions(
+    connection: &PgPool,
+    query: &str,
+    limit: i64,
+) -> Result<Vec<Suppression>> {
+    let pattern = format!(
+        "%{}%",
+        normalize(query)
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    sqlx::query_as!(
+        Suppression,
+        r#"
+        SELECT email, reason, source, note, created_at
+        FROM suppressions
+        WHERE email LIKE $1
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#,
+        pattern,
+        limit,
+    )
+    .fetch_all(connection)
+    .await
+    .context("Failed to search suppressions")
+}
+
+/// Checks the suppression list right before handing emails to `inner`, so that
+/// no code path can email a suppressed address.
+pub struct SuppressingEmailSender {
+    inner: Arc<dyn EmailSender>,
+    connection: PgPool,
+}
+
+impl SuppressingEmailSender {
+    pub fn new(inner: Arc<dyn EmailSender>, connection: PgPool) -> Self {
+        Self { inner, connection }
+    }
+}
+
+#[async_trait]
+impl EmailSender for SuppressingEmailSender {
+    async fn send_email(
+        &self,
+        recipient: SubscriberEmail,
+        message: &EmailMessage,
+    ) -> Result<DeliveryReceipt, SendEmailError> {
+        if is_suppressed(&self.connection, recipient.as_ref()).await? {
+            return Err(SendEmailError::Suppressed(recipient.as_ref().to_string()));
+        }
+        self.inner.send_email(recipient, message).await
+    }
+
+    async fn send_batch(
+        &self,
+        recipients: &[SubscriberEmail],
+        message: &EmailMessage,
+    ) -> Vec<RecipientOutcome> {
+        let emails: Vec<&str> = recipients.iter().map(AsRef::as_ref).collect();
+        let suppressed = match suppressed_among(&self.connection, &emails).await {
+            Ok(suppressed) => suppressed,
+            Err(e) => {
+                let e = SendEmailError::from(e);
+                return recipients
+                    .iter()
+                    .map(|recipient| RecipientOutcome {
+                        recipient: recipient.clone(),
+                        outcome: Err(e.duplicate()),
+                    })
+                    .collect();
+            }
+        };
+        let allowed: Vec<SubscriberEmail> = recipients
+            .iter()
+            .filter(|recipient| !suppressed.contains(recipient.as_ref()))
+            .cloned()
+            .collect();
+        let mut sent = self.inner.send_batch(&allowed, message).await.into_iter();
+        recipients
+            .iter()
+            .map(|recipient| {
+                if suppressed.contains(recipient.as_ref()) {
+                    RecipientOutcome {
+                        recipient: recipient.clone(),
+                        outcome: Err(SendEmailError::Suppressed(recipient.as_ref().to_string())),
+                    }
+                } else {
+                    sent.next()
+                        .unwrap_or_else(|| RecipientOutcome::missing(recipient))
+                }
+            })
+            .collect()
+    }
+
+    fn health(&self) -> Vec<ProviderHealth> {
+        self.inner.health()
+    }
+}
@@ -1,7 +1,12 @@
 use anyhow::Result;
-use tracing::{subscriber::set_global_default, Subscriber};
+use axum::http::Request;
+use once_cell::sync::OnceCell;
+use tracing::{subscriber::set_global_default, Span, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
-use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, EnvFilter, Registry};
+use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt, reload, EnvFilter, Registry};
+
+/// Swaps the filter of the first subscriber built by `get_subscriber`.
+static LOG_FILTER: OnceCell<reload::Handle<EnvFilter, Registry>> = OnceCell::new();
 
 // Creates a new file with filename timestamp_suffix.log in XDG_CACHE_HOME
 pub fn get_log_file(suffix: String) -> Result<std::fs::File> {
@@ -21,6 +26,8 @@ where
 {
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
+    let (env_filter, handle) = reload::Layer::new(env_filter);
+    let _ = LOG_FILTER.set(handle);
     let layer_bunyan = BunyanFormattingLayer::new(name, sink);
     let layer_stdout = tracing_subscriber::fmt::layer();
     Registry::default()
@@ -30,6 +37,45 @@ where
         .with(layer_stdout)
 }
 
+/// The span each request is handled in. It carries the id set by the request id
+/// layer, so everything logged while handling a request can be traced back to it.
+/// Only the path is recorded, query strings carry tokens like the webhook's.
+pub fn make_request_span<B>(request: &Request<B>) -> Span {
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|id| id.to_str().ok())
+        .unwrap_or_default();
+    tracing::info_span!(
+        "request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        request_id,
+    )
+}
+
+/// Replaces the log filter, unless `RUST_LOG` is set, which takes precedence as it
+/// does at startup.
+pub fn set_log_filter(env_filter: &str) -> Result<()> {
+    if std::env::var_os(EnvFilter::DEFAULT_ENV).is_some() {
+        tracing::warn!("Not changing the log filter to {env_filter:?}, RUST_LOG is set");
+        return Ok(());
+    }
+    if let Some(handle) = LOG_FILTER.get() {
+        handle.reload(EnvFilter::try_new(env_filter)?)?;
+    }
+    Ok(())
+}
+
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     set_global_default(subscriber).expect("Failed to set tracing subscriber");
 }
+
+pub fn spawn_blocking_with_tracing<F, R>(f: F) -> tokio::task::JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let current_span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || current_span.in_scope(f))
+}
@@ -0,0 +1,119 @@
+use std::{
+    net::TcpListener,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::State,
+    http::{header::HOST, uri::Authority, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use axum_server::{tls_rustls::RustlsConfig, Handle};
+use tokio_util::sync::CancellationToken;
+
+use crate::{configuration::TlsSettings, shutdown::Shutdown};
+
+/// Loads the certificate and key, and reloads them whenever the files change, so a
+/// renewed certificate is served without a restart.
+pub async fn load_tls_config(settings: &TlsSettings, shutdown: &Shutdown) -> Result<RustlsConfig> {
+    let config =
+        RustlsConfig::from_pem_file(&settings.certificate_path, &settings.private_key_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to load the TLS certificate {} and key {}",
+                    settings.certificate_path.display(),
+                    settings.private_key_path.display()
+                )
+            })?;
+    shutdown.spawn(reload_renewed_certificates(
+        config.clone(),
+        settings.clone(),
+        shutdown.stopping(),
+    ));
+    Ok(config)
+}
+
+fn modified(paths: [&PathBuf; 2]) -> Option<[SystemTime; 2]> {
+    let modified = |path: &Path| {
+        path.metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    Some([modified(paths[0])?, modified(paths[1])?])
+}
+
+async fn reload_renewed_certificates(
+    config: RustlsConfig,
+    settings: TlsSettings,
+    stopping: CancellationToken,
+) {
+    let paths = [&settings.certificate_path, &settings.private_key_path];
+    let mut interval = tokio::time::interval(settings.certificate_check_interval());
+    let mut loaded = modified(paths);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = stopping.cancelled() => return,
+        }
+        let current = modified(paths);
+        if current.is_none() || current == loaded {
+            continue;
+        }
+        match config.reload_from_pem_file(paths[0], paths[1]).await {
+            Ok(()) => {
+                tracing::info!("Reloaded the TLS certificate");
+                loaded = current;
+            }
+            // Likely caught halfway through a renewal, the next check tries again
+            Err(e) => tracing::warn!("Failed to reload the TLS certificate: {e}"),
+        }
+    }
+}
+
+/// Serves a plain HTTP listener that redirects every request to HTTPS on `https_port`.
+pub fn redirect_to_https(
+    listener: TcpListener,
+    https_port: u16,
+    handle: Handle,
+) -> impl std::future::Future<Output = std::io::Result<()>> {
+    let app = Router::new().fallback(redirect).with_state(https_port);
+    tracing::info!(
+        "redirecting {} to HTTPS",
+        listener
+            .local_addr()
+            .map(|address| address.to_string())
+            .unwrap_or_default()
+    );
+    axum_server::from_tcp(listener)
+        .handle(handle)
+        .serve(app.into_make_service())
+}
+
+/// The host comes from the request line or the `Host` header only. Axum's `Host`
+/// extractor would take `X-Forwarded-Host` first, which lets anyone pick where the
+/// redirect goes.
+async fn redirect(State(https_port): State<u16>, headers: HeaderMap, uri: Uri) -> Response {
+    let authority = match uri.authority() {
+        Some(authority) => Some(authority.clone()),
+        None => headers
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| host.parse::<Authority>().ok()),
+    };
+    let Some(authority) = authority else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let port = match https_port {
+        443 => String::new(),
+        port => format!(":{port}"),
+    };
+    let path = uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    Redirect::permanent(&format!("https://{}{port}{path}", authority.host())).into_response()
+}
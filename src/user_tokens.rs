@@ -0,0 +1,252 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::instrument;
+use uuid::Uuid;
+
+/// What a token in `user_tokens` grants its holder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Invitation,
+    PasswordReset,
+}
+
+impl TokenKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenKind::Invitation => "invitation",
+            TokenKind::PasswordReset => "password_reset",
+        }
+    }
+}
+
+/// A freshly generated token, as it appears in links: a random value followed by
+/// its signature. Only the hash of the random value is stored in the database.
+pub struct SignedToken {
+    pub token: String,
+    token_hash: String,
+}
+
+impl SignedToken {
+    pub fn generate(kind: TokenKind, hmac_secret: &Secret<String>) -> Result<Self> {
+        let value: String = thread_rng()
+            .sample_iter(Alphanumeric)
+            .map(char::from)
+            .take(32)
+            .collect();
+        let signature = hex::encode(signer(kind, &value, hmac_secret)?.finalize().into_bytes());
+        Ok(Self {
+            token: format!("{value}.{signature}"),
+            token_hash: hash(&value),
+        })
+    }
+
+    /// Checks the signature of a token received from a link, returning the hash to
+    /// look it up by. Forged or mangled tokens are rejected without touching the database.
+    pub fn verify(kind: TokenKind, token: &str, hmac_secret: &Secret<String>) -> Option<String> {
+        let (value, signature) = token.split_once('.')?;
+        let signature = hex::decode(signature).ok()?;
+        signer(kind, value, hmac_secret)
+            .ok()?
+            .verify_slice(&signature)
+            .ok()?;
+        Some(hash(value))
+    }
+}
+
+fn signer(kind: TokenKind, value: &str, hmac_secret: &Secret<String>) -> Result<Hmac<Sha256>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(hmac_secret.expose_secret().as_bytes())?;
+    mac.update(kind.as_str().as_bytes());
+    mac.update(b":");
+    mac.update(value.as_bytes());
+    Ok(mac)
+}
+
+fn hash(value: &str) -> String {
+    hex::encode(Sha256::digest(value.as_bytes()))
+}
+
+pub struct NewUserToken<'a> {
+    pub kind: TokenKind,
+    pub email: &'a str,
+    pub user_id: Option<Uuid>,
+    pub created_by: Option<Uuid>,
+    pub requested_from: Option<&'a str>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[instrument(name = "Store user token", skip(connection, token, new_token))]
+pub async fn store_token(
+    connection: &PgPool,
+    token: &SignedToken,
+    new_token: NewUserToken<'_>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO user_tokens
+            (token_hash, kind, email, user_id, created_by, requested_from, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+        token.token_hash,
+        new_token.kind.as_str(),
+        new_token.email,
+        new_token.user_id,
+        new_token.created_by,
+        new_token.requested_from,
+        Utc::now(),
+        new_token.expires_at,
+    )
+    .execute(connection)
+    .await
+    .context("Failed to store user token")?;
+    Ok(())
+}
+
+pub struct ConsumedToken {
+    pub email: String,
+    pub user_id: Option<Uuid>,
+}
+
+/// Marks an unexpired, unused token as used, so that each link works exactly once.
+#[instrument(name = "Consume user token", skip(transaction, token_hash))]
+pub async fn consume_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    kind: TokenKind,
+    token_hash: &str,
+) -> Result<Option<ConsumedToken>> {
+    let now = Utc::now();
+    let token = sqlx::query!(
+        r#"
+        UPDATE user_tokens
+        SET used_at = $3
+        WHERE token_hash = $1 AND kind = $2 AND used_at IS NULL AND expires_at > $3
+        RETURNING email, user_id
+        "#,
+        token_hash,
+        kind.as_str(),
+        now,
+    )
+    .fetch_optional(transaction)
+    .await
+    .context("Failed to consume user token")?
+    .map(|row| ConsumedToken {
+        email: row.email,
+        user_id: row.user_id,
+    });
+    Ok(token)
+}
+
+/// Counts tokens of `kind` issued for the email address or requested from the ip address
+/// during the last hour, whichever is higher.
+#[instrument(name = "Count recent user tokens", skip(connection))]
+pub async fn recent_token_count(
+    connection: &PgPool,
+    kind: TokenKind,
+    email: &str,
+    requested_from: &str,
+) -> Result<i64> {
+    let since = Utc::now() - chrono::Duration::hours(1);
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            COUNT(*) FILTER (WHERE lower(email) = lower($2)) AS "by_email!",
+            COUNT(*) FILTER (WHERE requested_from = $3) AS "by_ip!"
+        FROM user_tokens
+        WHERE kind = $1 AND created_at > $4
+        "#,
+        kind.as_str(),
+        email,
+        requested_from,
+        since,
+    )
+    .fetch_one(connection)
+    .await
+    .context("Failed to count recent user tokens")?;
+    Ok(row.by_email.max(row.by_ip))
+}
+
+/// Whether a token of `kind` that is neither used nor expired was issued for `email`.
+#[instrument(name = "Check for pending user token", skip(connection))]
+pub async fn pending_token_exists(
+    connection: &PgPool,
+    kind: TokenKind,
+    email: &str,
+) -> Result<bool> {
+    let exists = sqlx::query!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM user_tokens
+            WHERE kind = $1 AND lower(email) = lower($2) AND used_at IS NULL AND expires_at > now()
+        ) AS "exists!"
+        "#,
+        kind.as_str(),
+        email,
+    )
+    .fetch_one(connection)
+    .await
+    .context("Failed to check for pending user tokens")?
+    .exists;
+    Ok(exists)
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+
+    use crate::user_tokens::{SignedToken, TokenKind};
+
+    fn secret() -> Secret<String> {
+        Secret::new("a-secret-key".to_string())
+    }
+
+    #[test]
+    fn generated_tokens_pass_verification() {
+        let token = SignedToken::generate(TokenKind::Invitation, &secret()).unwrap();
+        assert_some_eq!(
+            SignedToken::verify(TokenKind::Invitation, &token.token, &secret()),
+            token.token_hash
+        );
+    }
+
+    #[test]
+    fn tokens_signed_with_another_key_are_rejected() {
+        let token = SignedToken::generate(TokenKind::Invitation, &secret()).unwrap();
+        let other_secret = Secret::new("another-secret-key".to_string());
+        assert_none!(SignedToken::verify(
+            TokenKind::Invitation,
+            &token.token,
+            &other_secret
+        ));
+    }
+
+    #[test]
+    fn tokens_are_bound_to_their_kind() {
+        let token = SignedToken::generate(TokenKind::Invitation, &secret()).unwrap();
+        assert_none!(SignedToken::verify(
+            TokenKind::PasswordReset,
+            &token.token,
+            &secret()
+        ));
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let token = SignedToken::generate(TokenKind::PasswordReset, &secret()).unwrap();
+        let tampered = format!("x{}", &token.token[1..]);
+        assert_none!(SignedToken::verify(
+            TokenKind::PasswordReset,
+            &tampered,
+            &secret()
+        ));
+        assert_none!(SignedToken::verify(
+            TokenKind::PasswordReset,
+            "not-a-token",
+            &secret()
+        ));
+    }
+}
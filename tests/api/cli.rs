@@ -0,0 +1,239 @@
+use uuid::Uuid;
+use zero2prod::{
+    configuration::get_configuration,
+    subscribers::import_subscribers,
+    suppressions::{suppress, SuppressionReason},
+};
+
+use crate::helpers::{create_database, run_cli, spawn_app, spawn_mailsink};
+
+fn stdout(output: &std::process::Output) -> String {
+    assert!(
+        output.status.success(),
+        "zero2prod failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[tokio::test]
+async fn migrate_creates_the_schema_of_an_empty_database() {
+    let mut config = get_configuration().unwrap().database;
+    config.name = Uuid::new_v4().to_string();
+    let connection = create_database(&config).await;
+
+    let output = run_cli(&["migrate"], &[("DATABASE__NAME", &config.name)], "").await;
+
+    stdout(&output);
+    sqlx::query("SELECT id FROM subscriptions")
+        .fetch_all(&connection)
+        .await
+        .expect("The subscriptions table is missing");
+}
+
+#[tokio::test]
+async fn database_settings_from_the_environment_win_over_database_url() {
+    let output = run_cli(
+        &["config", "show", "--format", "json"],
+        &[
+            ("DATABASE_URL", "postgres://app@db.internal:6432/newsletter"),
+            ("DATABASE__NAME", "explicit"),
+        ],
+        "",
+    )
+    .await;
+
+    let config: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+    let database = &config["database"];
+    assert_eq!(database["name"]["value"], "explicit");
+    assert_eq!(database["name"]["source"], "DATABASE__NAME");
+    assert_eq!(database["host"]["value"], "db.internal");
+    assert_eq!(database["host"]["source"], "database.url (DATABASE_URL)");
+}
+
+#[tokio::test]
+async fn migrate_is_a_no_op_on_an_up_to_date_database() {
+    let test_app = spawn_app().await;
+
+    let output = test_app.run_cli(&["migrate"], "").await;
+
+    assert!(stdout(&output).contains("up to date"));
+}
+
+#[tokio::test]
+async fn create_admin_creates_an_account_that_can_log_in() {
+    let test_app = spawn_app().await;
+    let password = Uuid::new_v4().to_string();
+
+    let output = test_app
+        .run_cli(
+            &[
+                "create-admin",
+                "--username",
+                "operator",
+                "--email",
+                "operator@example.com",
+            ],
+            &format!("{password}\n"),
+        )
+        .await;
+
+    stdout(&output);
+    let response = test_app.post_login("operator", &password).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let role = sqlx::query!("SELECT role FROM users WHERE username = 'operator'")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap()
+        .role;
+    assert_eq!(role, "admin");
+}
+
+#[tokio::test]
+async fn create_admin_rejects_a_weak_password() {
+    let test_app = spawn_app().await;
+
+    let output = test_app
+        .run_cli(
+            &[
+                "create-admin",
+                "--username",
+                "operator",
+                "--email",
+                "operator@example.com",
+                "--owner",
+            ],
+            "short\n",
+        )
+        .await;
+
+    assert!(!output.status.success());
+    let users = sqlx::query!("SELECT username FROM users WHERE username = 'operator'")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(users.is_empty());
+}
+
+#[tokio::test]
+async fn send_test_email_goes_through_the_configured_provider() {
+    let test_app = spawn_app().await;
+    let (address, sink) = spawn_mailsink();
+    let database = test_app.next_config.lock().unwrap().database.name.clone();
+
+    let output = run_cli(
+        &["send-test-email", "operator@example.com"],
+        &[
+            ("DATABASE__NAME", database.as_str()),
+            ("EMAIL_CLIENT__PROVIDER", "elastic_email"),
+            ("EMAIL_CLIENT__BASE_URL", address.as_str()),
+        ],
+        "",
+    )
+    .await;
+
+    stdout(&output);
+    let emails = sink.emails();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, "operator@example.com");
+}
+
+#[tokio::test]
+async fn exported_subscribers_can_be_imported_into_another_instance() {
+    let source = spawn_app().await;
+    for body in [
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "name=Tom&email=tom%40example.com",
+    ] {
+        source.post_subscription(body.into()).await;
+    }
+    let target = spawn_app().await;
+
+    let exported = stdout(&source.run_cli(&["subscribers", "export"], "").await);
+    let output = target.run_cli(&["subscribers", "import"], &exported).await;
+
+    assert!(stdout(&output).contains("Imported 2"));
+    let mut subscribers = sqlx::query!("SELECT email, name, status FROM subscriptions")
+        .fetch_all(&target.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.email, row.name, row.status))
+        .collect::<Vec<_>>();
+    subscribers.sort();
+    assert_eq!(
+        subscribers,
+        [
+            ("tom@example.com".into(), "Tom".into(), "confirmed".into()),
+            (
+                "ursula_le_guin@gmail.com".into(),
+                "le guin".into(),
+                "confirmed".into()
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn bounced_subscribers_are_not_reactivated_by_an_import() {
+    let source = spawn_app().await;
+    for body in [
+        "name=le%20guin&email=ursula_le_guin%40gmail.com",
+        "name=Tom&email=tom%40example.com",
+    ] {
+        source.post_subscription(body.into()).await;
+    }
+    source
+        .post_email_webhook(
+            "elastic_email",
+            "status=Error&category=NoMailbox&to=tom%40example.com",
+        )
+        .await;
+    let target = spawn_app().await;
+
+    let exported = stdout(&source.run_cli(&["subscribers", "export"], "").await);
+    let output = target.run_cli(&["subscribers", "import"], &exported).await;
+
+    assert!(stdout(&output).contains("Imported 1"));
+    assert!(stdout(&output).contains("1 not confirmed"));
+    let emails = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&target.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.email)
+        .collect::<Vec<_>>();
+    assert_eq!(emails, ["ursula_le_guin@gmail.com"]);
+}
+
+#[tokio::test]
+async fn import_skips_invalid_existing_and_suppressed_subscribers() {
+    let test_app = spawn_app().await;
+    test_app
+        .post_subscription("name=Tom&email=tom%40example.com".into())
+        .await;
+    suppress(
+        &test_app.db_pool,
+        "bounced@example.com",
+        SuppressionReason::HardBounce,
+        "test",
+        None,
+    )
+    .await
+    .unwrap();
+    let csv = "email,name\n\
+        new@example.com,New\n\
+        tom@example.com,Tom\n\
+        bounced@example.com,Bounced\n\
+        not-an-email,Invalid\n";
+
+    let report = import_subscribers(&test_app.db_pool, csv.as_bytes())
+        .await
+        .unwrap();
+
+    assert_eq!(report.imported, 1);
+    assert_eq!(report.already_subscribed, 1);
+    assert_eq!(report.suppressed, 1);
+    assert_eq!(report.invalid.len(), 1);
+    assert_eq!(report.invalid[0].0, 5);
+}
@@ -0,0 +1,30 @@
+use crate::helpers::spawn_app_with;
+
+#[tokio::test]
+async fn queries_running_past_the_statement_timeout_are_cancelled() {
+    let test_app = spawn_app_with(|c| c.database.statement_timeout_milliseconds = 100).await;
+
+    let result = sqlx::query("SELECT pg_sleep(2)")
+        .execute(&test_app.db_pool)
+        .await;
+
+    let error = result.unwrap_err();
+    assert!(
+        error.to_string().contains("statement timeout"),
+        "unexpected error: {error}"
+    );
+}
+
+#[tokio::test]
+async fn acquiring_a_connection_times_out_when_the_pool_is_exhausted() {
+    let test_app = spawn_app_with(|c| {
+        c.database.pool.max_connections = 1;
+        c.database.pool.acquire_timeout_milliseconds = 200;
+    })
+    .await;
+
+    let _only_connection = test_app.db_pool.acquire().await.unwrap();
+    let result = test_app.db_pool.acquire().await;
+
+    assert!(matches!(result, Err(sqlx::Error::PoolTimedOut)));
+}
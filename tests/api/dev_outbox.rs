@@ -0,0 +1,61 @@
+use uuid::Uuid;
+use zero2prod::{
+    configuration::{EmailProvider, Environment},
+    domain::SubscriberEmail,
+    email_client::{EmailMessage, EmailSender, OutboxEmailSender},
+};
+
+use crate::helpers::spawn_app_with;
+
+#[tokio::test]
+async fn dev_outbox_lists_captured_emails() {
+    let outbox = std::env::temp_dir().join(Uuid::new_v4().to_string());
+    let test_app = spawn_app_with(|c| {
+        c.email_client.provider = EmailProvider::Outbox;
+        c.email_client.outbox_directory = Some(outbox.clone());
+    })
+    .await;
+    let email_sender = OutboxEmailSender::new(
+        outbox,
+        SubscriberEmail::parse("sender@example.com".into()).unwrap(),
+    );
+    let subject = Uuid::new_v4().to_string();
+    email_sender
+        .send_email(
+            SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+            &EmailMessage::new(&subject, "<p>Hello</p>", "Hello"),
+        )
+        .await
+        .unwrap();
+
+    let response = reqwest::get(format!("{}/dev/outbox", test_app.address))
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(200, response.status().as_u16());
+    let body = response.text().await.unwrap();
+    assert!(body.contains(&subject));
+    assert!(body.contains("&lt;p&gt;Hello&lt;/p&gt;"));
+}
+
+#[tokio::test]
+async fn dev_outbox_is_only_available_with_the_outbox_provider() {
+    let test_app = spawn_app_with(|c| c.email_client.provider = EmailProvider::ElasticEmail).await;
+
+    let response = reqwest::get(format!("{}/dev/outbox", test_app.address))
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(404, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn dev_outbox_is_not_available_in_production() {
+    let test_app = spawn_app_with(|c| c.environment = Environment::Production).await;
+
+    let response = reqwest::get(format!("{}/dev/outbox", test_app.address))
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(404, response.status().as_u16());
+}
@@ -1,4 +1,10 @@
-use crate::helpers::spawn_app;
+use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+use zero2prod::{
+    domain::SubscriberEmail,
+    email_client::{EmailMessage, EmailSender},
+};
+
+use crate::helpers::{elastic_email_failover, spawn_app, spawn_app_with_email_sender};
 
 #[tokio::test]
 async fn health_check_works() {
@@ -14,3 +20,42 @@ async fn health_check_works() {
     assert!(response.status().is_success());
     assert_eq!(Some(0), response.content_length());
 }
+
+#[tokio::test]
+async fn readiness_is_ok_when_the_database_is_reachable() {
+    let test_app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/health_check/ready", test_app.address))
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(200, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["database"], "ok");
+}
+
+#[tokio::test]
+async fn readiness_fails_when_every_email_provider_circuit_is_open() {
+    let mock_server = MockServer::start().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&mock_server)
+        .await;
+    let email_client = elastic_email_failover(&mock_server, 1);
+    email_client
+        .send_email(
+            SubscriberEmail::parse("ursula@example.com".into()).unwrap(),
+            &EmailMessage::new("subject", "html", "text"),
+        )
+        .await
+        .unwrap_err();
+    let test_app = spawn_app_with_email_sender(email_client).await;
+
+    let response = reqwest::get(format!("{}/health_check/ready", test_app.address))
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(503, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["email_providers"][0]["circuit"], "open");
+}
@@ -1,9 +1,27 @@
+use std::{
+    net::TcpListener,
+    process::Stdio,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use once_cell::sync::Lazy;
+use secrecy::{ExposeSecret, Secret};
 use sqlx::{types::Uuid, Connection, Executor, PgConnection, PgPool};
+use tokio::io::AsyncWriteExt;
+use wiremock::MockServer;
 
 use zero2prod::{
-    configuration::{get_configuration, DatabaseSettings},
-    startup::{get_connection_pool, App},
+    authentication::compute_password_hash,
+    configuration::{get_configuration, CircuitBreakerSettings, DatabaseSettings, Settings},
+    domain::SubscriberEmail,
+    email_client::{
+        ElasticEmailClient, EmailSender, FailoverEmailSender, InMemoryEmailSender, RecordedEmail,
+        RetryPolicy,
+    },
+    mailsink::{run, MailSink},
+    shutdown::Shutdown,
+    startup::{get_connection_pool, run_migrations, App},
     telemetry::{get_log_file, get_subscriber, init_subscriber},
 };
 
@@ -18,44 +36,321 @@ static TRACING: Lazy<()> = Lazy::new(|| {
     init_subscriber(subscriber);
 });
 
+pub struct TestUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub password: String,
+    pub email: String,
+}
+
+impl TestUser {
+    pub fn generate() -> Self {
+        Self {
+            user_id: Uuid::new_v4(),
+            username: Uuid::new_v4().to_string(),
+            password: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", Uuid::new_v4()),
+        }
+    }
+
+    pub async fn store(&self, pool: &PgPool, role: &str) {
+        let password_hash = compute_password_hash(Secret::new(self.password.clone()))
+            .expect("Failed to hash password");
+        sqlx::query!(
+            r#"
+            INSERT INTO users (user_id, username, password_hash, email, role)
+            VALUES ($1, $2, $3, $4, $5)
+            "#,
+            self.user_id,
+            self.username,
+            password_hash.expose_secret(),
+            self.email,
+            role,
+        )
+        .execute(pool)
+        .await
+        .expect("Failed to store test user");
+    }
+}
+
 pub struct TestApp {
     pub address: String,
+    pub port: u16,
+    /// The plain HTTP listener redirecting to HTTPS, when TLS is configured.
+    pub redirect_port: Option<u16>,
     pub db_pool: PgPool,
+    pub email_sender: Arc<InMemoryEmailSender>,
+    pub test_user: TestUser,
+    pub email_webhook_token: Secret<String>,
+    /// What the application reads when it reloads its configuration.
+    pub next_config: Arc<Mutex<Settings>>,
+    pub shutdown: Shutdown,
 }
 
 impl TestApp {
+    /// Runs the `zero2prod` binary against this app's database, writing `stdin` to it.
+    pub async fn run_cli(&self, args: &[&str], stdin: &str) -> std::process::Output {
+        let database = self.next_config.lock().unwrap().database.name.clone();
+        run_cli(args, &[("DATABASE__NAME", database.as_str())], stdin).await
+    }
+
     pub async fn post_subscription(&self, body: String) -> reqwest::Response {
         reqwest::Client::new()
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
             .await
             .expect("Failed to send request")
     }
+
+    pub async fn post_login(&self, username: &str, password: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/login", &self.address))
+            .form(&[("username", username), ("password", password)])
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    pub async fn post_invitation(&self, user: &TestUser, email: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/admin/invitations", &self.address))
+            .basic_auth(&user.username, Some(&user.password))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    /// Requests a password reset and waits for the email to be sent, if there is one.
+    pub async fn post_password_reset(&self, email: &str) -> reqwest::Response {
+        let response = self.post_form("/password_reset", &[("email", email)]).await;
+        self.shutdown.wait_for_tasks().await;
+        response
+    }
+
+    pub async fn post_form(&self, path: &str, form: &[(&str, &str)]) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}{}", &self.address, path))
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    /// Posts a webhook notification to the URL providers are given, with the
+    /// configured token.
+    pub async fn post_email_webhook(&self, provider: &str, body: &str) -> reqwest::Response {
+        let token = self.email_webhook_token.expose_secret().clone();
+        self.post_email_webhook_with_token(provider, body, Some(&token))
+            .await
+    }
+
+    pub async fn post_email_webhook_with_token(
+        &self,
+        provider: &str,
+        body: &str,
+        token: Option<&str>,
+    ) -> reqwest::Response {
+        let mut request =
+            reqwest::Client::new().post(format!("{}/webhooks/email/{}", &self.address, provider));
+        if let Some(token) = token {
+            request = request.query(&[("token", token)]);
+        }
+        request
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body.to_string())
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    /// Changes `next_config` and asks the application to reload it.
+    pub async fn reload_config(&self, change: impl FnOnce(&mut Settings)) -> reqwest::Response {
+        change(&mut self.next_config.lock().unwrap());
+        reqwest::Client::new()
+            .post(format!("{}/admin/config/reload", &self.address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to send request")
+    }
+
+    /// Extracts the link sent in an email, pointed at the test server.
+    pub fn get_link(&self, email: &RecordedEmail) -> reqwest::Url {
+        self.get_link_in(&email.message.text_content)
+    }
+
+    pub fn get_link_in(&self, text: &str) -> reqwest::Url {
+        let link = text
+            .split_whitespace()
+            .find(|word| word.starts_with("http"))
+            .expect("No link in email");
+        let mut link = reqwest::Url::parse(link).unwrap();
+        assert_eq!(link.host_str().unwrap(), "127.0.0.1");
+        link.set_port(Some(self.port)).unwrap();
+        link
+    }
+}
+
+/// Extracts the `token` query parameter of a link.
+pub fn get_token(link: &reqwest::Url) -> String {
+    link.query_pairs()
+        .find(|(key, _)| key == "token")
+        .map(|(_, value)| value.into_owned())
+        .expect("No token in link")
+}
+
+/// Runs the `zero2prod` binary with extra environment variables, writing `stdin` to it.
+/// It doesn't inherit `DATABASE_URL`, so `DATABASE__NAME` picks the database.
+pub async fn run_cli(args: &[&str], env: &[(&str, &str)], stdin: &str) -> std::process::Output {
+    let mut child = tokio::process::Command::new(env!("CARGO_BIN_EXE_zero2prod"))
+        .args(args)
+        .env_remove("DATABASE_URL")
+        .envs(env.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to run zero2prod");
+    let mut input = child.stdin.take().unwrap();
+    input.write_all(stdin.as_bytes()).await.unwrap();
+    drop(input);
+    child
+        .wait_with_output()
+        .await
+        .expect("Failed to wait for zero2prod")
+}
+
+/// A mail sink on a random port, with its address.
+pub fn spawn_mailsink() -> (String, MailSink) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mail sink");
+    let address = format!("http://{}", listener.local_addr().unwrap());
+    let sink = MailSink::default();
+    tokio::spawn(run(listener, sink.clone()).expect("Failed to build mail sink"));
+    (address, sink)
+}
+
+/// Elastic Email at `mock_server` behind a circuit breaker, without retries.
+pub fn elastic_email_failover(
+    mock_server: &MockServer,
+    failure_threshold: u32,
+) -> Arc<FailoverEmailSender> {
+    let email_client = ElasticEmailClient::new(
+        mock_server.uri(),
+        SubscriberEmail::parse("newsletter@example.com".into()).unwrap(),
+        Secret::new("api-key".into()),
+        Duration::from_millis(200),
+        RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        },
+        50,
+    );
+    Arc::new(FailoverEmailSender::new(
+        vec![("elastic_email".into(), Arc::new(email_client))],
+        &CircuitBreakerSettings {
+            failure_threshold,
+            open_seconds: 60,
+        },
+    ))
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Like `spawn_app`, with a chance to adjust the configuration first.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
+    let email_sender = Arc::new(InMemoryEmailSender::new());
+    spawn(configure, Some(email_sender.clone()), email_sender).await
+}
+
+/// Like `spawn_app`, sending emails through `email_client`. `TestApp::email_sender` stays empty.
+pub async fn spawn_app_with_email_sender(email_client: Arc<dyn EmailSender>) -> TestApp {
+    spawn(
+        |_| {},
+        Some(email_client),
+        Arc::new(InMemoryEmailSender::new()),
+    )
+    .await
+}
+
+/// Like `spawn_app_with`, sending emails through the email client the configuration
+/// describes, as the application does outside tests. `TestApp::email_sender` stays empty.
+pub async fn spawn_app_with_configured_email_sender(
+    configure: impl FnOnce(&mut Settings),
+) -> TestApp {
+    spawn(configure, None, Arc::new(InMemoryEmailSender::new())).await
+}
+
+async fn spawn(
+    configure: impl FnOnce(&mut Settings),
+    email_client: Option<Arc<dyn EmailSender>>,
+    email_sender: Arc<InMemoryEmailSender>,
+) -> TestApp {
     Lazy::force(&TRACING);
 
     let config = {
         let mut c = get_configuration().expect("Failed to read configuration");
         c.database.name = Uuid::new_v4().to_string();
         c.application.port = 0;
+        c.email_client.outbox_directory = Some(std::env::temp_dir().join("zero2prod-outbox"));
+        configure(&mut c);
         c
     };
     configure_database(&config.database).await;
 
-    let app = App::build(&config).await.expect("Failed to build server");
-    let address = format!("http://127.0.0.1:{}", app.port());
-    let _ = tokio::spawn(app.run_until_stopped());
-    TestApp {
+    let next_config = Arc::new(Mutex::new(config.clone()));
+    let load = {
+        let next_config = next_config.clone();
+        Arc::new(move || {
+            let config = next_config.lock().unwrap().clone();
+            config.validate()?;
+            Ok(config)
+        })
+    };
+    let app = match email_client {
+        Some(email_client) => App::build_with_email_sender(&config, email_client, load).await,
+        None => App::build(&config, load).await,
+    }
+    .expect("Failed to build server");
+    let port = app.port();
+    let redirect_port = app.redirect_port();
+    let shutdown = app.shutdown();
+    let address = match config.application.tls {
+        Some(_) => format!("https://localhost:{}", port),
+        None => format!("http://127.0.0.1:{}", port),
+    };
+    // Never stopped, so that a stray SIGTERM still ends the test run
+    tokio::spawn(app.run_until(std::future::pending()));
+    let test_app = TestApp {
         address,
+        port,
+        redirect_port,
         db_pool: get_connection_pool(&config.database),
-    }
+        email_sender,
+        test_user: TestUser::generate(),
+        email_webhook_token: config.email_webhooks.token.clone(),
+        next_config,
+        shutdown,
+    };
+    test_app.test_user.store(&test_app.db_pool, "owner").await;
+    test_app
 }
 
 async fn configure_database(config: &DatabaseSettings) -> PgPool {
+    let connection_pool = create_database(config).await;
+    run_migrations(&connection_pool)
+        .await
+        .expect("Failed to migrate the database");
+    connection_pool
+}
+
+/// Creates the database, without migrating it.
+pub async fn create_database(config: &DatabaseSettings) -> PgPool {
     let mut connection = PgConnection::connect_with(&config.without_db())
         .await
         .expect("Failed to connect to postgres");
@@ -64,12 +359,7 @@ async fn configure_database(config: &DatabaseSettings) -> PgPool {
         .await
         .expect("Failed to create database");
 
-    let connection_pool = PgPool::connect_with(config.with_db())
-        .await
-        .expect("Failed to create database");
-    sqlx::migrate!("./migrations")
-        .run(&connection_pool)
+    PgPool::connect_with(config.with_db())
         .await
-        .expect("Failed to migrate the database");
-    connection_pool
+        .expect("Failed to create database")
 }
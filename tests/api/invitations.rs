@@ -0,0 +1,156 @@
+use crate::helpers::{get_token, spawn_app, TestApp, TestUser};
+
+async fn accept_invitation(test_app: &TestApp, token: &str, username: &str) -> reqwest::Response {
+    test_app
+        .post_form(
+            "/invitations/accept",
+            &[
+                ("token", token),
+                ("username", username),
+                ("password", "an-acceptably-long-password"),
+            ],
+        )
+        .await
+}
+
+#[tokio::test]
+async fn owners_can_invite_admins_who_then_can_log_in() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_invitation(&test_app.test_user, "ursula@example.com")
+        .await;
+    assert_eq!(200, response.status().as_u16());
+
+    let sent_emails = test_app.email_sender.sent_emails();
+    assert_eq!(1, sent_emails.len());
+    assert_eq!("ursula@example.com", sent_emails[0].recipient);
+    let link = test_app.get_link(&sent_emails[0]);
+    let form_page = reqwest::get(link.clone()).await.unwrap();
+    assert_eq!(200, form_page.status().as_u16());
+
+    let token = get_token(&link);
+    let response = accept_invitation(&test_app, &token, "ursula").await;
+    assert_eq!(200, response.status().as_u16());
+
+    let response = test_app
+        .post_login("ursula", "an-acceptably-long-password")
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT email, role FROM users WHERE username = 'ursula'")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch invited user");
+    assert_eq!(saved.email.as_deref(), Some("ursula@example.com"));
+    assert_eq!(saved.role, "admin");
+}
+
+#[tokio::test]
+async fn invitation_links_can_only_be_used_once() {
+    let test_app = spawn_app().await;
+
+    test_app
+        .post_invitation(&test_app.test_user, "ursula@example.com")
+        .await;
+    let email = &test_app.email_sender.sent_emails()[0];
+    let token = get_token(&test_app.get_link(email));
+
+    let response = accept_invitation(&test_app, &token, "ursula").await;
+    assert_eq!(200, response.status().as_u16());
+    let response = accept_invitation(&test_app, &token, "someone-else").await;
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn expired_invitations_are_rejected() {
+    let test_app = spawn_app().await;
+
+    test_app
+        .post_invitation(&test_app.test_user, "ursula@example.com")
+        .await;
+    let email = &test_app.email_sender.sent_emails()[0];
+    let token = get_token(&test_app.get_link(email));
+    sqlx::query!("UPDATE user_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let response = accept_invitation(&test_app, &token, "ursula").await;
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn forged_invitation_tokens_are_rejected() {
+    let test_app = spawn_app().await;
+
+    let response = accept_invitation(&test_app, "abcdef.0123456789", "ursula").await;
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn admins_cannot_invite_other_admins() {
+    let test_app = spawn_app().await;
+    let admin = TestUser::generate();
+    admin.store(&test_app.db_pool, "admin").await;
+
+    let response = test_app.post_invitation(&admin, "ursula@example.com").await;
+
+    assert_eq!(403, response.status().as_u16());
+    assert!(test_app.email_sender.sent_emails().is_empty());
+}
+
+#[tokio::test]
+async fn inviting_requires_authentication() {
+    let test_app = spawn_app().await;
+    let stranger = TestUser::generate();
+
+    let response = test_app
+        .post_invitation(&stranger, "ursula@example.com")
+        .await;
+
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(
+        r#"Basic realm="admin""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[tokio::test]
+async fn inviting_an_existing_users_email_is_rejected() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_invitation(&test_app.test_user, &test_app.test_user.email)
+        .await;
+
+    assert_eq!(409, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn inviting_an_email_with_a_pending_invitation_is_rejected() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_invitation(&test_app.test_user, "invitee@example.com")
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    let response = test_app
+        .post_invitation(&test_app.test_user, "invitee@example.com")
+        .await;
+
+    assert_eq!(409, response.status().as_u16());
+    assert_eq!(1, test_app.email_sender.sent_emails().len());
+}
+
+#[tokio::test]
+async fn inviting_an_invalid_email_is_rejected() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_invitation(&test_app.test_user, "not-an-email")
+        .await;
+
+    assert_eq!(400, response.status().as_u16());
+}
@@ -0,0 +1,175 @@
+use uuid::Uuid;
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn login_returns_200_for_valid_credentials() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_login(&test_app.test_user.username, &test_app.test_user.password)
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn login_returns_401_for_an_invalid_password() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_login(&test_app.test_user.username, "wrong password")
+        .await;
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn login_returns_401_for_an_unknown_username() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_login(&Uuid::new_v4().to_string(), &test_app.test_user.password)
+        .await;
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn account_is_locked_out_after_too_many_failed_attempts() {
+    let test_app = spawn_app().await;
+    let username = &test_app.test_user.username;
+
+    for _ in 0..5 {
+        let response = test_app.post_login(username, "wrong password").await;
+        assert_eq!(401, response.status().as_u16());
+    }
+
+    // Even the correct password is rejected while the account is locked out
+    let response = test_app
+        .post_login(username, &test_app.test_user.password)
+        .await;
+
+    assert_eq!(429, response.status().as_u16());
+    let retry_after: u64 = response
+        .headers()
+        .get("Retry-After")
+        .expect("Missing Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 30);
+}
+
+#[tokio::test]
+async fn unknown_usernames_are_locked_out_like_known_ones() {
+    let test_app = spawn_app().await;
+    let username = Uuid::new_v4().to_string();
+
+    for _ in 0..5 {
+        test_app.post_login(&username, "wrong password").await;
+    }
+    let response = test_app.post_login(&username, "wrong password").await;
+
+    assert_eq!(429, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn successful_login_resets_the_failed_attempt_count() {
+    let test_app = spawn_app().await;
+    let username = &test_app.test_user.username;
+
+    for _ in 0..4 {
+        test_app.post_login(username, "wrong password").await;
+    }
+    let response = test_app
+        .post_login(username, &test_app.test_user.password)
+        .await;
+    assert_eq!(200, response.status().as_u16());
+
+    for _ in 0..4 {
+        test_app.post_login(username, "wrong password").await;
+    }
+    let response = test_app
+        .post_login(username, &test_app.test_user.password)
+        .await;
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn ip_is_locked_out_after_too_many_failed_attempts_across_accounts() {
+    let test_app = spawn_app().await;
+
+    for _ in 0..20 {
+        let response = test_app
+            .post_login(&Uuid::new_v4().to_string(), "wrong password")
+            .await;
+        assert_eq!(401, response.status().as_u16());
+    }
+
+    let response = test_app
+        .post_login(&test_app.test_user.username, &test_app.test_user.password)
+        .await;
+
+    assert_eq!(429, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn behind_a_trusted_proxy_clients_are_locked_out_by_their_forwarded_address() {
+    let test_app = spawn_app_with(|c| {
+        c.application.client_ip.trusted_proxies = vec!["127.0.0.0/8".parse().unwrap()]
+    })
+    .await;
+    let login_from = |client: &'static str, username: String, password: String| {
+        reqwest::Client::new()
+            .post(format!("{}/login", &test_app.address))
+            .header("X-Forwarded-For", format!("{client}, 127.0.0.2"))
+            .form(&[("username", username), ("password", password)])
+            .send()
+    };
+
+    for _ in 0..20 {
+        let response = login_from(
+            "203.0.113.1",
+            Uuid::new_v4().to_string(),
+            "wrong password".into(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(401, response.status().as_u16());
+    }
+
+    let username = test_app.test_user.username.clone();
+    let password = test_app.test_user.password.clone();
+    let attacker = login_from("203.0.113.1", username.clone(), password.clone())
+        .await
+        .unwrap();
+    let someone_else = login_from("203.0.113.2", username, password).await.unwrap();
+    assert_eq!(429, attacker.status().as_u16());
+    assert_eq!(200, someone_else.status().as_u16());
+}
+
+#[tokio::test]
+async fn login_attempts_are_recorded_in_the_audit_log() {
+    let test_app = spawn_app().await;
+    let username = &test_app.test_user.username;
+
+    test_app.post_login(username, "wrong password").await;
+    test_app
+        .post_login(username, &test_app.test_user.password)
+        .await;
+
+    let events = sqlx::query!(
+        "SELECT event FROM login_audit_log WHERE username = $1 ORDER BY occurred_at",
+        username
+    )
+    .fetch_all(&test_app.db_pool)
+    .await
+    .expect("Failed to fetch audit log")
+    .into_iter()
+    .map(|r| r.event)
+    .collect::<Vec<_>>();
+
+    assert_eq!(events, vec!["login_failed", "login_succeeded"]);
+}
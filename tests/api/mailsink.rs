@@ -0,0 +1,52 @@
+use std::{sync::Arc, time::Duration};
+
+use secrecy::Secret;
+use zero2prod::{
+    domain::SubscriberEmail,
+    email_client::{ElasticEmailClient, RetryPolicy},
+};
+
+use crate::helpers::{get_token, spawn_app_with_email_sender, spawn_mailsink};
+
+#[tokio::test]
+async fn password_reset_emails_sent_through_elastic_email_arrive_in_the_mail_sink() {
+    let (address, sink) = spawn_mailsink();
+    let email_client = ElasticEmailClient::new(
+        address,
+        SubscriberEmail::parse("newsletter@example.com".into()).unwrap(),
+        Secret::new("api-key".into()),
+        Duration::from_secs(2),
+        RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        },
+        50,
+    );
+    let test_app = spawn_app_with_email_sender(Arc::new(email_client)).await;
+
+    let response = test_app
+        .post_password_reset(&test_app.test_user.email)
+        .await;
+    assert_eq!(200, response.status().as_u16());
+
+    let emails = sink.emails();
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, test_app.test_user.email);
+    let link = test_app.get_link_in(emails[0].body_text.as_deref().unwrap());
+    let response = test_app
+        .post_form(
+            "/password_reset/confirm",
+            &[
+                ("token", get_token(&link).as_str()),
+                ("password", "a-brand-new-password"),
+            ],
+        )
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    let delivery = sqlx::query!("SELECT message_id FROM email_deliveries")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.message_id, Some(emails[0].message_id.clone()));
+}
@@ -1,3 +1,16 @@
+mod cli;
+mod database;
+mod dev_outbox;
 mod health_check;
 mod helpers;
+mod invitations;
+mod login;
+mod mailsink;
+mod metrics;
+mod password_reset;
+mod reload;
+mod shutdown;
 mod subscriptions;
+mod suppressions;
+mod tls;
+mod webhooks;
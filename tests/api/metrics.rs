@@ -0,0 +1,22 @@
+use wiremock::MockServer;
+
+use crate::helpers::{elastic_email_failover, spawn_app_with_email_sender};
+
+#[tokio::test]
+async fn metrics_expose_the_email_provider_circuit_state() {
+    let mock_server = MockServer::start().await;
+    let test_app = spawn_app_with_email_sender(elastic_email_failover(&mock_server, 5)).await;
+
+    let response = reqwest::get(format!("{}/metrics", test_app.address))
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(200, response.status().as_u16());
+    let body = response.text().await.unwrap();
+    assert!(body.contains(
+        r#"email_provider_circuit_state{position="0",provider="elastic_email",state="closed"} 1"#
+    ));
+    assert!(body.contains(
+        r#"email_provider_circuit_state{position="0",provider="elastic_email",state="open"} 0"#
+    ));
+}
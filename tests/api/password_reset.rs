@@ -0,0 +1,163 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use secrecy::Secret;
+use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+use zero2prod::{
+    domain::SubscriberEmail,
+    email_client::{ElasticEmailClient, RetryPolicy},
+};
+
+use crate::helpers::{get_token, spawn_app, spawn_app_with_email_sender, TestApp};
+
+async fn confirm_reset(test_app: &TestApp, token: &str, password: &str) -> reqwest::Response {
+    test_app
+        .post_form(
+            "/password_reset/confirm",
+            &[("token", token), ("password", password)],
+        )
+        .await
+}
+
+async fn request_reset_token(test_app: &TestApp) -> String {
+    let response = test_app
+        .post_password_reset(&test_app.test_user.email)
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    let sent_emails = test_app.email_sender.sent_emails();
+    get_token(&test_app.get_link(sent_emails.last().unwrap()))
+}
+
+#[tokio::test]
+async fn password_can_be_reset_through_the_emailed_link() {
+    let test_app = spawn_app().await;
+
+    let token = request_reset_token(&test_app).await;
+    let response = confirm_reset(&test_app, &token, "a-brand-new-password").await;
+    assert_eq!(200, response.status().as_u16());
+
+    let username = &test_app.test_user.username;
+    let response = test_app.post_login(username, "a-brand-new-password").await;
+    assert_eq!(200, response.status().as_u16());
+    let response = test_app
+        .post_login(username, &test_app.test_user.password)
+        .await;
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn emails_are_matched_whatever_their_case() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_password_reset(&test_app.test_user.email.to_uppercase())
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(test_app.email_sender.sent_emails().len(), 1);
+}
+
+#[tokio::test]
+async fn unknown_emails_get_the_same_response_but_no_email() {
+    let test_app = spawn_app().await;
+
+    let response = test_app.post_password_reset("nobody@example.com").await;
+
+    assert_eq!(200, response.status().as_u16());
+    assert!(test_app.email_sender.sent_emails().is_empty());
+}
+
+#[tokio::test]
+async fn known_emails_are_answered_without_waiting_for_the_email_to_be_sent() {
+    let mock_server = MockServer::start().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500).set_delay(Duration::from_secs(3)))
+        .mount(&mock_server)
+        .await;
+    let email_client = ElasticEmailClient::new(
+        mock_server.uri(),
+        SubscriberEmail::parse("newsletter@example.com".into()).unwrap(),
+        Secret::new("api-key".into()),
+        Duration::from_secs(10),
+        RetryPolicy {
+            max_attempts: 1,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        },
+        50,
+    );
+    let test_app = spawn_app_with_email_sender(Arc::new(email_client)).await;
+
+    let started = Instant::now();
+    let response = test_app
+        .post_form("/password_reset", &[("email", &test_app.test_user.email)])
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn password_reset_requests_are_rate_limited() {
+    let test_app = spawn_app().await;
+
+    for _ in 0..5 {
+        let response = test_app
+            .post_password_reset(&test_app.test_user.email)
+            .await;
+        assert_eq!(200, response.status().as_u16());
+    }
+    assert_eq!(3, test_app.email_sender.sent_emails().len());
+}
+
+#[tokio::test]
+async fn reset_links_can_only_be_used_once() {
+    let test_app = spawn_app().await;
+
+    let token = request_reset_token(&test_app).await;
+    let response = confirm_reset(&test_app, &token, "a-brand-new-password").await;
+    assert_eq!(200, response.status().as_u16());
+    let response = confirm_reset(&test_app, &token, "another-new-password").await;
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn expired_reset_links_are_rejected() {
+    let test_app = spawn_app().await;
+
+    let token = request_reset_token(&test_app).await;
+    sqlx::query!("UPDATE user_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    let response = confirm_reset(&test_app, &token, "a-brand-new-password").await;
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn short_passwords_are_rejected() {
+    let test_app = spawn_app().await;
+
+    let token = request_reset_token(&test_app).await;
+    let response = confirm_reset(&test_app, &token, "short").await;
+
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn resetting_the_password_lifts_an_account_lockout() {
+    let test_app = spawn_app().await;
+    let username = &test_app.test_user.username;
+    for _ in 0..5 {
+        test_app.post_login(username, "wrong password").await;
+    }
+
+    let token = request_reset_token(&test_app).await;
+    confirm_reset(&test_app, &token, "a-brand-new-password").await;
+    let response = test_app.post_login(username, "a-brand-new-password").await;
+
+    assert_eq!(200, response.status().as_u16());
+}
@@ -0,0 +1,124 @@
+use secrecy::Secret;
+use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+use zero2prod::configuration::EmailProvider;
+
+use crate::helpers::{spawn_app, spawn_app_with_configured_email_sender};
+
+#[tokio::test]
+async fn reloaded_login_limits_apply_to_the_next_request() {
+    let test_app = spawn_app().await;
+    let username = &test_app.test_user.username;
+
+    let response = test_app
+        .reload_config(|c| c.login.max_failed_attempts_per_account = 1)
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        report["applied"],
+        serde_json::json!(["login.max_failed_attempts_per_account"])
+    );
+    assert_eq!(report["requires_restart"], serde_json::json!([]));
+
+    let response = test_app.post_login(username, "wrong-password").await;
+    assert_eq!(401, response.status().as_u16());
+    let response = test_app
+        .post_login(username, &test_app.test_user.password)
+        .await;
+    assert_eq!(429, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn changes_that_need_a_restart_are_reported_but_not_applied() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .reload_config(|c| {
+            c.application.port = 9999;
+            c.database.pool.max_connections = 3;
+        })
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["applied"], serde_json::json!([]));
+    assert_eq!(
+        report["requires_restart"],
+        serde_json::json!(["application.port", "database.pool.max_connections"])
+    );
+    let response = reqwest::get(format!("{}/health_check", test_app.address))
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+}
+
+#[tokio::test]
+async fn an_invalid_configuration_is_rejected_and_the_current_one_kept() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .reload_config(|c| {
+            c.login.max_failed_attempts_per_account = 1;
+            c.accounts.invitation_expiry_hours = 0;
+        })
+        .await;
+
+    assert_eq!(422, response.status().as_u16());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["error"]
+        .as_str()
+        .unwrap()
+        .contains("accounts.invitation_expiry_hours"));
+    let response = test_app
+        .post_login(&test_app.test_user.username, "wrong-password")
+        .await;
+    assert_eq!(401, response.status().as_u16());
+    let response = test_app
+        .post_login(&test_app.test_user.username, &test_app.test_user.password)
+        .await;
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn reloading_requires_authentication() {
+    let test_app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/admin/config/reload", test_app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn a_reloaded_email_api_key_is_used_for_the_next_email() {
+    let mock_server = MockServer::start().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "success": true,
+            "data": {"transactionid": "transaction-id", "messageid": "message-id"}
+        })))
+        .mount(&mock_server)
+        .await;
+    let test_app = spawn_app_with_configured_email_sender(|c| {
+        c.email_client.provider = EmailProvider::ElasticEmail;
+        c.email_client.base_url = mock_server.uri();
+        c.email_client.api_key = Secret::new("old-key".into());
+    })
+    .await;
+
+    let response = test_app
+        .reload_config(|c| c.email_client.api_key = Secret::new("new-key".into()))
+        .await;
+    assert_eq!(200, response.status().as_u16());
+    test_app
+        .post_password_reset(&test_app.test_user.email)
+        .await;
+
+    let requests = mock_server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    let body = String::from_utf8_lossy(&requests[0].body);
+    assert!(body.contains("Apikey=new-key"), "Sent with {body}");
+}
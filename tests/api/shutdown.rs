@@ -0,0 +1,149 @@
+use std::{
+    net::TcpListener,
+    process::{ExitStatus, Stdio},
+    time::Duration,
+};
+
+use tokio::process::{Child, Command};
+use wiremock::{matchers::any, Mock, MockServer, ResponseTemplate};
+
+use crate::helpers::{spawn_app, TestApp};
+
+/// The `zero2prod` binary serving `test_app`'s database, sending emails through
+/// `mock_server`. It doesn't inherit `DATABASE_URL`, so `DATABASE__NAME` picks the database.
+struct Server {
+    address: String,
+    process: Child,
+}
+
+impl Server {
+    async fn start(test_app: &TestApp, mock_server: &MockServer, shutdown_timeout: u64) -> Self {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let database = test_app.next_config.lock().unwrap().database.name.clone();
+        let process = Command::new(env!("CARGO_BIN_EXE_zero2prod"))
+            .arg("serve")
+            .env("APPLICATION__PORT", port.to_string())
+            .env(
+                "APPLICATION__SHUTDOWN_TIMEOUT_SECONDS",
+                shutdown_timeout.to_string(),
+            )
+            .env_remove("DATABASE_URL")
+            .env("DATABASE__NAME", database)
+            .env("EMAIL_CLIENT__PROVIDER", "elastic_email")
+            .env("EMAIL_CLIENT__BASE_URL", mock_server.uri())
+            .env("EMAIL_CLIENT__TIMEOUT_MILLISECONDS", "30000")
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .expect("Failed to run zero2prod");
+        let server = Self {
+            address: format!("http://127.0.0.1:{port}"),
+            process,
+        };
+        for _ in 0..100 {
+            if reqwest::get(format!("{}/health_check", server.address))
+                .await
+                .is_ok()
+            {
+                return server;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        panic!("zero2prod did not start listening");
+    }
+
+    fn terminate(&self) {
+        let status = std::process::Command::new("kill")
+            .args(["-TERM", &self.process.id().unwrap().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    async fn wait(&mut self, timeout: Duration) -> ExitStatus {
+        tokio::time::timeout(timeout, self.process.wait())
+            .await
+            .expect("zero2prod did not exit in time")
+            .unwrap()
+    }
+}
+
+/// Answers like Elastic Email, after `delay`.
+async fn slow_email_provider(delay: Duration) -> MockServer {
+    let mock_server = MockServer::start().await;
+    Mock::given(any())
+        .respond_with(
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({
+                    "success": true,
+                    "data": {"transactionid": "transaction-id", "messageid": "message-id"}
+                }))
+                .set_delay(delay),
+        )
+        .mount(&mock_server)
+        .await;
+    mock_server
+}
+
+/// Invites an admin, which sends an email, and waits until the email is being sent.
+async fn request_in_flight(
+    test_app: &TestApp,
+    server: &Server,
+    mock_server: &MockServer,
+) -> tokio::task::JoinHandle<reqwest::Result<reqwest::Response>> {
+    let request = reqwest::Client::new()
+        .post(format!("{}/admin/invitations", server.address))
+        .basic_auth(
+            &test_app.test_user.username,
+            Some(&test_app.test_user.password),
+        )
+        .form(&[("email", "new-admin@example.com")])
+        .send();
+    let request = tokio::spawn(request);
+    for _ in 0..100 {
+        if !mock_server.received_requests().await.unwrap().is_empty() {
+            return request;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("The email was never sent");
+}
+
+#[tokio::test]
+async fn sigterm_lets_in_flight_requests_finish() {
+    let test_app = spawn_app().await;
+    let mock_server = slow_email_provider(Duration::from_secs(2)).await;
+    let mut server = Server::start(&test_app, &mock_server, 10).await;
+    let request = request_in_flight(&test_app, &server, &mock_server).await;
+
+    server.terminate();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let refused = reqwest::get(format!("{}/health_check", server.address)).await;
+    assert!(refused.is_err(), "New connections are still accepted");
+    let response = request.await.unwrap().expect("The request was cut off");
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(server.wait(Duration::from_secs(5)).await.success());
+    let deliveries = sqlx::query!("SELECT recipient FROM email_deliveries")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 1);
+}
+
+#[tokio::test]
+async fn requests_still_running_at_the_shutdown_timeout_are_cut_off() {
+    let test_app = spawn_app().await;
+    let mock_server = slow_email_provider(Duration::from_secs(30)).await;
+    let mut server = Server::start(&test_app, &mock_server, 1).await;
+    let request = request_in_flight(&test_app, &server, &mock_server).await;
+
+    server.terminate();
+
+    assert!(server.wait(Duration::from_secs(5)).await.success());
+    assert!(request.await.unwrap().is_err());
+}
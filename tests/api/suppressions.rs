@@ -0,0 +1,182 @@
+use crate::helpers::{spawn_app, TestApp};
+
+async fn post_suppression(test_app: &TestApp, email: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/admin/suppressions", &test_app.address))
+        .basic_auth(
+            &test_app.test_user.username,
+            Some(&test_app.test_user.password),
+        )
+        .form(&[("email", email), ("note", "Asked to never hear from us")])
+        .send()
+        .await
+        .expect("Failed to send request")
+}
+
+async fn search_suppressions(test_app: &TestApp, search: &str) -> serde_json::Value {
+    let response = reqwest::Client::new()
+        .get(format!("{}/admin/suppressions", &test_app.address))
+        .basic_auth(
+            &test_app.test_user.username,
+            Some(&test_app.test_user.password),
+        )
+        .query(&[("search", search)])
+        .send()
+        .await
+        .expect("Failed to send request");
+    assert_eq!(200, response.status().as_u16());
+    response.json().await.unwrap()
+}
+
+async fn delete_suppression(test_app: &TestApp, email: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .delete(format!(
+            "{}/admin/suppressions/{}",
+            &test_app.address, email
+        ))
+        .basic_auth(
+            &test_app.test_user.username,
+            Some(&test_app.test_user.password),
+        )
+        .send()
+        .await
+        .expect("Failed to send request")
+}
+
+#[tokio::test]
+async fn admins_can_add_search_and_remove_suppressions() {
+    let test_app = spawn_app().await;
+
+    let response = post_suppression(&test_app, "Ursula@Example.com").await;
+    assert_eq!(200, response.status().as_u16());
+    let response = post_suppression(&test_app, "ursula@example.com").await;
+    assert_eq!(409, response.status().as_u16());
+
+    let found = search_suppressions(&test_app, "URSULA").await;
+    assert_eq!(found.as_array().unwrap().len(), 1);
+    assert_eq!(found[0]["email"], "ursula@example.com");
+    assert_eq!(found[0]["reason"], "manual");
+    assert_eq!(
+        found[0]["source"],
+        format!("admin:{}", test_app.test_user.user_id)
+    );
+    assert_eq!(found[0]["note"], "Asked to never hear from us");
+    assert!(search_suppressions(&test_app, "le_guin")
+        .await
+        .as_array()
+        .unwrap()
+        .is_empty());
+
+    let response = delete_suppression(&test_app, "ursula@example.com").await;
+    assert_eq!(200, response.status().as_u16());
+    let response = delete_suppression(&test_app, "ursula@example.com").await;
+    assert_eq!(404, response.status().as_u16());
+    assert!(search_suppressions(&test_app, "")
+        .await
+        .as_array()
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn suppression_endpoints_require_authentication() {
+    let test_app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/admin/suppressions", &test_app.address))
+        .form(&[("email", "ursula@example.com")])
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(401, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn suppressed_addresses_are_not_subscribed() {
+    let test_app = spawn_app().await;
+    post_suppression(&test_app, "ursula_le_guin@gmail.com").await;
+
+    let response = test_app
+        .post_subscription("name=le%20guin&email=Ursula_Le_Guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    let subscriptions = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(subscriptions.is_empty());
+}
+
+#[tokio::test]
+async fn suppressed_addresses_are_not_emailed() {
+    let test_app = spawn_app().await;
+    post_suppression(&test_app, &test_app.test_user.email).await;
+
+    let response = test_app
+        .post_password_reset(&test_app.test_user.email)
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    assert!(test_app.email_sender.sent_emails().is_empty());
+}
+
+#[tokio::test]
+async fn hard_bounces_and_complaints_are_suppressed() {
+    let test_app = spawn_app().await;
+
+    test_app
+        .post_email_webhook(
+            "elastic_email",
+            "to=ursula%40example.com&status=Error&category=NoMailbox",
+        )
+        .await;
+    test_app
+        .post_email_webhook(
+            "elastic_email",
+            "to=le_guin%40example.com&status=AbuseReport",
+        )
+        .await;
+    test_app
+        .post_email_webhook(
+            "elastic_email",
+            "to=earthsea%40example.com&status=Error&category=Timeout",
+        )
+        .await;
+
+    let found = search_suppressions(&test_app, "example.com").await;
+    let mut found: Vec<(&str, &str, &str)> = found
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| {
+            (
+                entry["email"].as_str().unwrap(),
+                entry["reason"].as_str().unwrap(),
+                entry["source"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    found.sort();
+    assert_eq!(
+        found,
+        vec![
+            ("le_guin@example.com", "complaint", "webhook:elastic_email"),
+            ("ursula@example.com", "hard_bounce", "webhook:elastic_email"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn suppressed_addresses_cannot_be_invited() {
+    let test_app = spawn_app().await;
+    post_suppression(&test_app, "ursula@example.com").await;
+
+    let response = test_app
+        .post_invitation(&test_app.test_user, "ursula@example.com")
+        .await;
+
+    assert_eq!(422, response.status().as_u16());
+    assert!(test_app.email_sender.sent_emails().is_empty());
+}
@@ -0,0 +1,144 @@
+use std::{path::PathBuf, time::Duration};
+
+use reqwest::{redirect, Certificate};
+use uuid::Uuid;
+use zero2prod::configuration::TlsSettings;
+
+use crate::helpers::spawn_app_with;
+
+/// A self-signed certificate for localhost, written to temporary files.
+struct SelfSigned {
+    certificate_path: PathBuf,
+    private_key_path: PathBuf,
+}
+
+impl SelfSigned {
+    fn generate() -> Self {
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir(&directory).unwrap();
+        let certificate = Self {
+            certificate_path: directory.join("cert.pem"),
+            private_key_path: directory.join("key.pem"),
+        };
+        certificate.renew();
+        certificate
+    }
+
+    /// Overwrites the files with a new certificate and returns it.
+    fn renew(&self) -> Certificate {
+        let certificate =
+            rcgen::generate_simple_self_signed(vec!["localhost".into(), "127.0.0.1".into()])
+                .unwrap();
+        let pem = certificate.serialize_pem().unwrap();
+        std::fs::write(
+            &self.private_key_path,
+            certificate.serialize_private_key_pem(),
+        )
+        .unwrap();
+        std::fs::write(&self.certificate_path, &pem).unwrap();
+        Certificate::from_pem(pem.as_bytes()).unwrap()
+    }
+
+    fn read(&self) -> Certificate {
+        Certificate::from_pem(&std::fs::read(&self.certificate_path).unwrap()).unwrap()
+    }
+
+    fn settings(&self, redirect_port: Option<u16>) -> TlsSettings {
+        TlsSettings {
+            certificate_path: self.certificate_path.clone(),
+            private_key_path: self.private_key_path.clone(),
+            redirect_port,
+            certificate_check_seconds: 1,
+        }
+    }
+}
+
+fn client_trusting(certificate: Certificate) -> reqwest::Client {
+    reqwest::Client::builder()
+        .add_root_certificate(certificate)
+        .redirect(redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn serves_https_with_the_configured_certificate() {
+    let certificate = SelfSigned::generate();
+    let test_app = spawn_app_with(|c| c.application.tls = Some(certificate.settings(None))).await;
+
+    let response = client_trusting(certificate.read())
+        .get(format!("{}/health_check", test_app.address))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert!(test_app.address.starts_with("https://"));
+    assert!(response.status().is_success());
+}
+
+#[tokio::test]
+async fn plain_http_is_redirected_to_https() {
+    let certificate = SelfSigned::generate();
+    let test_app =
+        spawn_app_with(|c| c.application.tls = Some(certificate.settings(Some(0)))).await;
+    let redirect_port = test_app.redirect_port.expect("No redirect listener");
+
+    let response = client_trusting(certificate.read())
+        .get(format!(
+            "http://localhost:{redirect_port}/health_check?probe=1"
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 308);
+    assert_eq!(
+        response.headers()["Location"],
+        format!("https://localhost:{}/health_check?probe=1", test_app.port).as_str()
+    );
+}
+
+#[tokio::test]
+async fn the_https_redirect_ignores_forwarded_hosts() {
+    let certificate = SelfSigned::generate();
+    let test_app =
+        spawn_app_with(|c| c.application.tls = Some(certificate.settings(Some(0)))).await;
+    let redirect_port = test_app.redirect_port.expect("No redirect listener");
+
+    let response = client_trusting(certificate.read())
+        .get(format!("http://localhost:{redirect_port}/health_check"))
+        .header("X-Forwarded-Host", "evil.example")
+        .header("Forwarded", "host=evil.example")
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 308);
+    assert_eq!(
+        response.headers()["Location"],
+        format!("https://localhost:{}/health_check", test_app.port).as_str()
+    );
+}
+
+#[tokio::test]
+async fn a_renewed_certificate_is_served_without_a_restart() {
+    let certificate = SelfSigned::generate();
+    let test_app = spawn_app_with(|c| c.application.tls = Some(certificate.settings(None))).await;
+    let renewed = client_trusting(certificate.renew());
+
+    let mut served = false;
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        if renewed
+            .get(format!("{}/health_check", test_app.address))
+            .send()
+            .await
+            .is_ok()
+        {
+            served = true;
+            break;
+        }
+    }
+
+    assert!(served, "The renewed certificate was never served");
+}
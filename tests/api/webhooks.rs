@@ -0,0 +1,194 @@
+use zero2prod::{email_client::DeliveryReceipt, email_events::record_delivery};
+
+use crate::helpers::{spawn_app, TestApp};
+
+const EMAIL: &str = "ursula_le_guin@gmail.com";
+
+async fn subscribe(test_app: &TestApp) {
+    let response = test_app
+        .post_subscription("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+    assert_eq!(200, response.status().as_u16());
+}
+
+async fn subscriber_status(test_app: &TestApp) -> (String, i32) {
+    let row = sqlx::query!(
+        "SELECT status, soft_bounces FROM subscriptions WHERE email = $1",
+        EMAIL
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .unwrap();
+    (row.status, row.soft_bounces)
+}
+
+fn notification(status: &str, category: &str) -> String {
+    format!("to=ursula_le_guin%40gmail.com&status={status}&category={category}&transaction=t-1")
+}
+
+#[tokio::test]
+async fn a_hard_bounce_marks_the_subscriber_as_bounced() {
+    let test_app = spawn_app().await;
+    subscribe(&test_app).await;
+
+    let response = test_app
+        .post_email_webhook("elastic_email", &notification("Error", "NoMailbox"))
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(subscriber_status(&test_app).await.0, "bounced");
+}
+
+#[tokio::test]
+async fn notifications_match_the_subscriber_whatever_the_case_of_the_address() {
+    let test_app = spawn_app().await;
+    subscribe(&test_app).await;
+
+    let response = test_app
+        .post_email_webhook(
+            "elastic_email",
+            "to=Ursula_Le_Guin%40Gmail.com&status=Error&category=NoMailbox&transaction=t-1",
+        )
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(subscriber_status(&test_app).await.0, "bounced");
+}
+
+#[tokio::test]
+async fn a_complaint_marks_the_subscriber_as_complained() {
+    let test_app = spawn_app().await;
+    subscribe(&test_app).await;
+
+    let response = test_app
+        .post_email_webhook("elastic_email", &notification("AbuseReport", ""))
+        .await;
+
+    assert_eq!(200, response.status().as_u16());
+    assert_eq!(subscriber_status(&test_app).await.0, "complained");
+}
+
+#[tokio::test]
+async fn soft_bounces_are_counted_up_to_the_threshold() {
+    let test_app = spawn_app().await;
+    subscribe(&test_app).await;
+
+    for (i, category) in ["GreyListed", "Timeout"].iter().enumerate() {
+        test_app
+            .post_email_webhook("elastic_email", &notification("Error", category))
+            .await;
+        assert_eq!(
+            subscriber_status(&test_app).await,
+            ("confirmed".into(), i as i32 + 1)
+        );
+    }
+    test_app
+        .post_email_webhook("elastic_email", &notification("Error", "Throttling"))
+        .await;
+
+    assert_eq!(subscriber_status(&test_app).await, ("bounced".into(), 3));
+    let suppression = sqlx::query!("SELECT reason FROM suppressions WHERE email = $1", EMAIL)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(suppression.reason, "soft_bounce");
+}
+
+#[tokio::test]
+async fn a_delivery_resets_the_soft_bounce_count() {
+    let test_app = spawn_app().await;
+    subscribe(&test_app).await;
+
+    test_app
+        .post_email_webhook("elastic_email", &notification("Error", "Timeout"))
+        .await;
+    test_app
+        .post_email_webhook("elastic_email", &notification("Sent", ""))
+        .await;
+
+    assert_eq!(subscriber_status(&test_app).await, ("confirmed".into(), 0));
+}
+
+#[tokio::test]
+async fn redelivered_webhooks_are_only_counted_once() {
+    let test_app = spawn_app().await;
+    subscribe(&test_app).await;
+
+    for _ in 0..3 {
+        let response = test_app
+            .post_email_webhook("elastic_email", &notification("Error", "Timeout"))
+            .await;
+        assert_eq!(200, response.status().as_u16());
+    }
+
+    assert_eq!(subscriber_status(&test_app).await, ("confirmed".into(), 1));
+}
+
+#[tokio::test]
+async fn events_are_recorded_against_their_delivery() {
+    let test_app = spawn_app().await;
+    let receipt = DeliveryReceipt {
+        message_id: None,
+        transaction_id: Some("t-1".into()),
+    };
+    record_delivery(&test_app.db_pool, EMAIL, &receipt)
+        .await
+        .unwrap();
+
+    test_app
+        .post_email_webhook("elastic_email", &notification("Sent", ""))
+        .await;
+
+    let event = sqlx::query!(
+        r#"
+        SELECT e.kind, d.recipient
+        FROM email_events e JOIN email_deliveries d ON d.id = e.delivery_id
+        "#
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(event.kind, "delivered");
+    assert_eq!(event.recipient, EMAIL);
+}
+
+#[tokio::test]
+async fn webhooks_with_an_invalid_token_are_rejected() {
+    let test_app = spawn_app().await;
+    subscribe(&test_app).await;
+
+    let response = test_app
+        .post_email_webhook_with_token(
+            "elastic_email",
+            &notification("Error", "NoMailbox"),
+            Some("not-the-token"),
+        )
+        .await;
+
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(subscriber_status(&test_app).await.0, "confirmed");
+}
+
+#[tokio::test]
+async fn webhooks_without_a_token_are_rejected() {
+    let test_app = spawn_app().await;
+    subscribe(&test_app).await;
+
+    let response = test_app
+        .post_email_webhook_with_token("elastic_email", &notification("Error", "NoMailbox"), None)
+        .await;
+
+    assert_eq!(401, response.status().as_u16());
+    assert_eq!(subscriber_status(&test_app).await.0, "confirmed");
+}
+
+#[tokio::test]
+async fn webhooks_for_unknown_providers_are_not_found() {
+    let test_app = spawn_app().await;
+
+    let response = test_app
+        .post_email_webhook("carrier_pigeon", &notification("Sent", ""))
+        .await;
+
+    assert_eq!(404, response.status().as_u16());
+}